@@ -0,0 +1,81 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::executor::Executor;
+
+/// Files and directories whose contents decide what Maven produces.
+const INPUTS: &[&str] = &["pom.xml", "src"];
+
+/// Remembers what the last successful build was made from, so an unchanged
+/// project does not have to be rebuilt. The fingerprint lives next to the
+/// WAR as `target/<war>.war.fingerprint`.
+pub struct BuildCache {
+    war: PathBuf,
+    stamp: PathBuf,
+}
+
+impl BuildCache {
+    pub fn new(target_dir: &str, war_name: &str) -> Self {
+        let war = Path::new(target_dir).join(format!("{}.war", war_name));
+        let stamp = Path::new(target_dir).join(format!("{}.war.fingerprint", war_name));
+        BuildCache { war, stamp }
+    }
+
+    /// Whether the WAR exists and was built from sources matching `fingerprint`.
+    pub fn is_fresh(&self, fingerprint: &str) -> bool {
+        self.war.exists()
+            && fs::read_to_string(&self.stamp)
+                .map(|stamp| stamp.trim() == fingerprint)
+                .unwrap_or(false)
+    }
+
+    pub fn store(&self, exec: &Executor, fingerprint: &str) -> Result<()> {
+        exec.write(&self.stamp, &format!("{}\n", fingerprint))
+    }
+}
+
+/// Hashes the build inputs below `root` together with `settings`, the Maven
+/// options that change the output (such as the profile).
+pub fn fingerprint(root: &Path, settings: &[String]) -> Result<String> {
+    let mut files = Vec::new();
+    for input in INPUTS {
+        collect(&root.join(input), &mut files)?;
+    }
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for setting in settings {
+        hasher.update(setting.as_bytes());
+        hasher.update([0]);
+    }
+    for file in files {
+        let contents = fs::read(&file)?;
+        let relative = file.strip_prefix(root).unwrap_or(&file);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+fn collect(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let Ok(metadata) = fs::metadata(path) else {
+        return Ok(());
+    };
+    if metadata.is_file() {
+        files.push(path.to_path_buf());
+    } else if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            collect(&entry?.path(), files)?;
+        }
+    }
+    Ok(())
+}
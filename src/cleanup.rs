@@ -0,0 +1,140 @@
+use colored::*;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::error::Result;
+use crate::executor::Executor;
+
+/// What a single cleanup pattern removed.
+pub struct Removed {
+    pub pattern: String,
+    pub matches: u64,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Removes files and directories by glob pattern and keeps count of what
+/// went away. A pattern ending in `/*` empties the directory but keeps it.
+pub struct Cleanup<'a> {
+    exec: &'a Executor,
+    removed: Vec<Removed>,
+}
+
+impl<'a> Cleanup<'a> {
+    pub fn new(exec: &'a Executor) -> Self {
+        Cleanup {
+            exec,
+            removed: Vec::new(),
+        }
+    }
+
+    pub fn remove<P: AsRef<Path>>(&mut self, pattern: P) -> Result<()> {
+        let pattern = pattern.as_ref().to_string_lossy().into_owned();
+        let paths = glob::glob(&pattern).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid cleanup pattern '{}': {}", pattern, e),
+            )
+        })?;
+
+        let mut removed = Removed {
+            pattern,
+            matches: 0,
+            files: 0,
+            bytes: 0,
+        };
+        for path in paths {
+            let path = path.map_err(|e| io::Error::new(e.error().kind(), e.to_string()))?;
+            let (files, bytes) = measure(&path)?;
+            self.exec.remove(&path)?;
+            removed.matches += 1;
+            removed.files += files;
+            removed.bytes += bytes;
+        }
+
+        self.removed.push(removed);
+        Ok(())
+    }
+
+    /// Prints how many files and bytes each pattern removed.
+    pub fn report(&self) {
+        let width = self
+            .removed
+            .iter()
+            .map(|r| r.pattern.len())
+            .max()
+            .unwrap_or(0);
+        for removed in &self.removed {
+            let line = format!(
+                "  {:width$}  {}",
+                removed.pattern,
+                if removed.matches == 0 {
+                    "nothing to remove".to_string()
+                } else {
+                    format!("{}, {}", files(removed.files), format_bytes(removed.bytes))
+                },
+                width = width
+            );
+            if removed.matches == 0 {
+                println!("{}", line.dimmed());
+            } else {
+                println!("{}", line);
+            }
+        }
+
+        let total: u64 = self.removed.iter().map(|r| r.files).sum();
+        let bytes: u64 = self.removed.iter().map(|r| r.bytes).sum();
+        println!(
+            "{}",
+            format!(
+                "{} {}, {}",
+                if self.exec.is_dry_run() {
+                    "Would remove"
+                } else {
+                    "Removed"
+                },
+                files(total),
+                format_bytes(bytes)
+            )
+            .yellow()
+        );
+    }
+}
+
+/// `1 file`, `3 files`.
+fn files(count: u64) -> String {
+    format!("{} file{}", count, if count == 1 { "" } else { "s" })
+}
+
+/// Counts the files below `path` and their total size without following
+/// symlinks.
+fn measure(path: &Path) -> io::Result<(u64, u64)> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok((1, metadata.len()));
+    }
+
+    let mut files = 0;
+    let mut bytes = 0;
+    for entry in fs::read_dir(path)? {
+        let (f, b) = measure(&entry?.path())?;
+        files += f;
+        bytes += b;
+    }
+    Ok((files, bytes))
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
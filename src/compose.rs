@@ -0,0 +1,238 @@
+use serde_json::Value;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::error::{Result, RunappError};
+use crate::executor::Executor;
+
+/// The file names Compose looks for, in the order it prefers them.
+const FILES: &[&str] = &[
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+
+/// Which Compose CLI is installed.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Cli {
+    /// The `docker compose` plugin.
+    V2,
+    /// The standalone `docker-compose`.
+    V1,
+}
+
+impl fmt::Display for Cli {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Cli::V2 => write!(f, "docker compose"),
+            Cli::V1 => write!(f, "docker-compose"),
+        }
+    }
+}
+
+/// A service of the compose project and whether it is running.
+pub struct Service {
+    pub name: String,
+    pub running: bool,
+}
+
+/// The compose project runapp works on. With `docker.compose_project`
+/// set, every command is scoped to that name with `--project-name`;
+/// otherwise Compose picks the project as it does for a plain
+/// `docker compose` in `root`.
+pub struct Compose {
+    cli: Cli,
+    root: PathBuf,
+    file: Option<PathBuf>,
+    project: Option<String>,
+}
+
+impl Compose {
+    /// Finds the Compose CLI and the compose file, `file` if it is set or
+    /// the first of the standard names below `root` otherwise. An empty
+    /// `project` leaves the project name to Compose.
+    pub fn detect(exec: &Executor, root: &Path, file: &str, project: &str) -> Result<Self> {
+        let cli = if works(exec, Command::new("docker").args(["compose", "version"])) {
+            Cli::V2
+        } else if works(exec, Command::new("docker-compose").arg("version")) {
+            Cli::V1
+        } else {
+            return Err(RunappError::CommandNotFound {
+                name: "docker compose".to_string(),
+                setting: None,
+            });
+        };
+
+        let file = if file.is_empty() {
+            FILES
+                .iter()
+                .map(|name| root.join(name))
+                .find(|path| path.is_file())
+        } else {
+            Some(root.join(file))
+        };
+
+        Ok(Compose {
+            cli,
+            root: root.to_path_buf(),
+            file,
+            // Compose only accepts lowercase project names
+            project: (!project.is_empty()).then(|| project.to_lowercase()),
+        })
+    }
+
+    pub fn cli(&self) -> Cli {
+        self.cli
+    }
+
+    /// The project's name: `docker.compose_project` when set, otherwise the
+    /// one Compose picks, from the compose file's `name:` or its directory.
+    pub fn project(&self, exec: &Executor) -> String {
+        if let Some(project) = &self.project {
+            return project.clone();
+        }
+        if self.cli == Cli::V2 && self.file.is_some() {
+            let name = self
+                .lines(exec, &["config", "--format", "json"])
+                .ok()
+                .and_then(|lines| serde_json::from_str::<Value>(&lines.join("\n")).ok())
+                .and_then(|config| config.get("name")?.as_str().map(str::to_string));
+            if let Some(name) = name {
+                return name;
+            }
+        }
+        let dir = self
+            .file
+            .as_deref()
+            .and_then(Path::parent)
+            .unwrap_or(&self.root);
+        // What Compose does with the directory name: lowercase it and drop
+        // what a project name cannot contain (v1 also drops '-' and '_')
+        dir.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase()
+            .chars()
+            .filter(|c| {
+                c.is_ascii_alphanumeric() || (self.cli == Cli::V2 && matches!(c, '-' | '_'))
+            })
+            .collect()
+    }
+
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = match self.cli {
+            Cli::V2 => {
+                let mut command = Command::new("docker");
+                command.arg("compose");
+                command
+            }
+            Cli::V1 => Command::new("docker-compose"),
+        };
+        if let Some(project) = &self.project {
+            command.arg("--project-name").arg(project);
+        }
+        if let Some(file) = &self.file {
+            command.arg("--file").arg(file);
+        }
+        command.args(args);
+        command
+    }
+
+    /// Fails unless there is a compose file to work with.
+    fn require_file(&self) -> Result<()> {
+        if self.file.is_none() {
+            return Err(RunappError::Config(format!(
+                "no compose file found (looked for {}); set docker.compose_file",
+                FILES.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// The services the compose file defines, with their state.
+    pub fn services(&self, exec: &Executor) -> Result<Vec<Service>> {
+        self.require_file()?;
+        let defined = self.lines(exec, &["config", "--services"])?;
+        let running = self.lines(exec, &["ps", "--services", "--filter", "status=running"])?;
+        Ok(defined
+            .into_iter()
+            .map(|name| Service {
+                running: running.contains(&name),
+                name,
+            })
+            .collect())
+    }
+
+    fn lines(&self, exec: &Executor, args: &[&str]) -> Result<Vec<String>> {
+        let mut command = self.command(args);
+        let output = exec.probe(command.stderr(Stdio::piped()))?;
+        if !output.status.success() {
+            return Err(RunappError::CommandFailed {
+                name: self.cli.to_string(),
+                code: output.status.code(),
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Starts `services` in the background, or every service if it is empty.
+    /// Names the compose file does not define are rejected up front.
+    pub fn up(&self, exec: &Executor, services: &[String]) -> Result<()> {
+        let defined: Vec<String> = self
+            .services(exec)?
+            .into_iter()
+            .map(|service| service.name)
+            .collect();
+        if let Some(unknown) = services.iter().find(|name| !defined.contains(name)) {
+            return Err(RunappError::Config(format!(
+                "the compose file has no service '{}' (it defines: {})",
+                unknown,
+                defined.join(", ")
+            )));
+        }
+
+        let mut command = self.command(&["up", "--detach"]);
+        command.args(services);
+        exec.run(&mut command)
+    }
+
+    /// Shows the logs of `service`, following them with `follow`.
+    pub fn logs(&self, exec: &Executor, service: &str, follow: bool) -> Result<()> {
+        self.require_file()?;
+        let mut command = self.command(&["logs"]);
+        if follow {
+            command.arg("--follow");
+        }
+        command.arg(service);
+        exec.run(&mut command)
+    }
+
+    /// Stops and removes the project's containers and networks.
+    pub fn down(&self, exec: &Executor) -> Result<()> {
+        exec.run(&mut self.command(&["down"]))
+    }
+
+    /// Whether `down` can find the project: v2 finds a named project by its
+    /// name alone, otherwise it takes the compose file.
+    pub fn can_down(&self) -> bool {
+        (self.cli == Cli::V2 && self.project.is_some()) || self.file.is_some()
+    }
+}
+
+fn works(exec: &Executor, command: &mut Command) -> bool {
+    exec.probe(command.stdout(Stdio::null()).stderr(Stdio::null()))
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
@@ -0,0 +1,337 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::{Result, RunappError};
+use crate::register::Register;
+
+pub const PROJECT_FILE: &str = "runapp.toml";
+
+// Every setting runapp understands, with the value used when no layer overrides it.
+const DEFAULTS: &[(&str, &str)] = &[
+    ("ports.http", "8080"),
+    ("ports.debug", "8000"),
+    ("ports.mysql", "3306"),
+    ("paths.mysql_dir", "mysql"),
+    ("paths.compile_log", "tomcat/compile_log.txt"),
+    ("paths.target_dir", "target"),
+    ("paths.db_source_dir", "src/main/resources/db/application"),
+    ("paths.catalina_pid", "tomcat/catalina.pid"),
+    ("maven.command", "mvn"),
+    ("maven.goal", "package"),
+    ("maven.profile", ""),
+    ("maven.offline", "false"),
+    ("maven.skip_tests", "true"),
+    ("maven.args", ""),
+    ("commands.mysqlinit", "mysqlinit"),
+    ("commands.mysqlinit_remote", "mysqlinit_remote"),
+    ("commands.mysqlcred", "mysqlcred"),
+    ("commands.mysql_infile", "mysql_infile"),
+    ("commands.mysql_drop", "mysql_drop"),
+    ("commands.start_mysql", "start_mysql"),
+    ("commands.stop_mysql", "stop_mysql"),
+    ("commands.auth_server_run", "auth-server-run"),
+    ("commands.auth_server_stop", "auth-server-stop"),
+    ("commands.auth_server_logs", "auth-server-logs"),
+    ("commands.pdp_docker_run", "pdp-docker-run"),
+    ("commands.pdp_docker_stop", "pdp-docker-stop"),
+    ("ports.container_http", "8080"),
+    ("docker.container", ""),
+    ("docker.network", "bridge"),
+    ("docker.compose_file", ""),
+    ("docker.compose_project", ""),
+    ("tomcat.start_timeout_secs", "120"),
+    ("debug.enabled", "true"),
+    ("debug.suspend", "false"),
+    ("tomcat.stop_timeout_secs", "20"),
+    ("health.timeout_secs", "60"),
+    ("health.backoff_initial_ms", "100"),
+    ("health.backoff_max_ms", "2000"),
+    ("health.auth_server_url", ""),
+    ("health.pdp_url", ""),
+    ("health.app_path", ""),
+    ("services.auth_server_process", "auth-server"),
+    ("services.pdp_container", "pdp"),
+];
+
+// Settings that only accept `true` or `false`.
+const FLAGS: &[&str] = &[
+    "maven.offline",
+    "maven.skip_tests",
+    "debug.enabled",
+    "debug.suspend",
+];
+
+// Settings that only accept one of a few values.
+const CHOICES: &[(&str, &[&str])] = &[("docker.network", &["bridge", "host"])];
+
+/// Where the effective value of a setting came from.
+#[derive(Clone, Debug)]
+pub enum Source {
+    Default,
+    Register(PathBuf),
+    Project(PathBuf),
+    User(PathBuf),
+    Env(String),
+    Cli(String),
+    /// Picked when the configured port was taken.
+    PortCheck,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::Register(path) => write!(f, "register {}", path.display()),
+            Source::Project(path) => write!(f, "project file {}", path.display()),
+            Source::User(path) => write!(f, "user file {}", path.display()),
+            Source::Env(var) => write!(f, "environment variable {}", var),
+            Source::Cli(flag) => write!(f, "command line {}", flag),
+            Source::PortCheck => write!(f, "picked by the port check"),
+        }
+    }
+}
+
+pub struct Setting {
+    pub value: String,
+    pub source: Source,
+}
+
+/// The merged configuration. Later layers win: defaults, then the register
+/// definition, then the project `runapp.toml`, then the user file, then
+/// `RUNAPP_*` environment variables and finally command line overrides.
+pub struct Config {
+    values: BTreeMap<&'static str, Setting>,
+}
+
+impl Config {
+    pub fn load(cli_overrides: &[(String, String, String)]) -> Result<Config> {
+        let mut config = Config {
+            values: DEFAULTS
+                .iter()
+                .map(|&(key, value)| {
+                    (
+                        key,
+                        Setting {
+                            value: value.to_string(),
+                            source: Source::Default,
+                        },
+                    )
+                })
+                .collect(),
+        };
+
+        let project_file = PathBuf::from(PROJECT_FILE);
+        config.apply_file(&project_file, Source::Project(project_file.clone()))?;
+
+        if let Some(user_file) = user_file() {
+            config.apply_file(&user_file, Source::User(user_file.clone()))?;
+        }
+
+        for &(key, _) in DEFAULTS {
+            let var = env_var_name(key);
+            if let Ok(value) = env::var(&var) {
+                config
+                    .set(key, &value, Source::Env(var.clone()))
+                    .map_err(|e| invalid(format!("{}: {}", var, e)))?;
+            }
+        }
+
+        for (flag, key, value) in cli_overrides {
+            config
+                .set(key, value, Source::Cli(flag.clone()))
+                .map_err(|e| invalid(format!("{}: {}", flag, e)))?;
+        }
+
+        Ok(config)
+    }
+
+    fn apply_file(&mut self, path: &Path, source: Source) -> Result<()> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(invalid(format!("{}: {}", path.display(), e))),
+        };
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e| invalid(format!("{}: {}", path.display(), e)))?;
+
+        let mut entries = Vec::new();
+        flatten("", &toml::Value::Table(table), &mut entries);
+        for (key, value) in entries {
+            self.set(&key, &value, source.clone())
+                .map_err(|e| invalid(format!("{}: {}", path.display(), e)))?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies the ports declared by the register to settings no other layer
+    /// has overridden.
+    pub fn apply_register(&mut self, register: &Register) {
+        let ports = [
+            ("ports.http", register.http_port),
+            ("ports.debug", register.debug_port),
+        ];
+        for (key, port) in ports {
+            let Some(port) = port else { continue };
+            let setting = self.values.get_mut(key).expect("known setting");
+            if let Source::Default = setting.source {
+                setting.value = port.to_string();
+                setting.source = Source::Register(register.source.clone());
+            }
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str, source: Source) -> std::result::Result<(), String> {
+        let setting = self
+            .values
+            .get_mut(key)
+            .ok_or_else(|| format!("unknown setting '{}'", key))?;
+        if key.starts_with("ports.") && value.parse::<u16>().is_err() {
+            return Err(format!("'{}' is not a valid port for '{}'", value, key));
+        }
+        if (key.ends_with("_secs") || key.ends_with("_ms")) && value.parse::<u64>().is_err() {
+            return Err(format!("'{}' is not a valid number for '{}'", value, key));
+        }
+        if FLAGS.contains(&key) && value.parse::<bool>().is_err() {
+            return Err(format!("'{}' is not true or false for '{}'", value, key));
+        }
+        if let Some((_, choices)) = CHOICES.iter().find(|(choice, _)| *choice == key) {
+            if !choices.contains(&value) {
+                return Err(format!(
+                    "'{}' is not one of {} for '{}'",
+                    value,
+                    choices.join(", "),
+                    key
+                ));
+            }
+        }
+        setting.value = value.to_string();
+        setting.source = source;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> &str {
+        &self.values[key].value
+    }
+
+    pub fn http_port(&self) -> u16 {
+        self.port("ports.http")
+    }
+
+    pub fn debug_port(&self) -> u16 {
+        self.port("ports.debug")
+    }
+
+    pub fn port(&self, key: &str) -> u16 {
+        self.get(key).parse().expect("validated when set")
+    }
+
+    /// Moves a `ports.*` setting to a port chosen at run time.
+    pub fn set_picked(&mut self, key: &str, port: u16) {
+        self.set(key, &port.to_string(), Source::PortCheck)
+            .expect("ports are known settings");
+    }
+
+    /// A numeric setting such as `health.timeout_secs`.
+    pub fn number(&self, key: &str) -> u64 {
+        self.get(key).parse().expect("validated when set")
+    }
+
+    /// A `true`/`false` setting such as `maven.offline`.
+    pub fn flag(&self, key: &str) -> bool {
+        self.get(key).parse().expect("validated when set")
+    }
+
+    pub fn mysql_dir(&self) -> &str {
+        self.get("paths.mysql_dir")
+    }
+
+    pub fn compile_log(&self) -> &str {
+        self.get("paths.compile_log")
+    }
+
+    pub fn target_dir(&self) -> &str {
+        self.get("paths.target_dir")
+    }
+
+    pub fn db_source_dir(&self) -> &str {
+        self.get("paths.db_source_dir")
+    }
+
+    pub fn catalina_pid(&self) -> &str {
+        self.get("paths.catalina_pid")
+    }
+
+    /// A `*_secs` setting as a duration.
+    pub fn seconds(&self, key: &str) -> Duration {
+        Duration::from_secs(self.number(key))
+    }
+
+    /// Name of a helper binary, e.g. `command("start_mysql")`.
+    pub fn command(&self, name: &str) -> &str {
+        self.get(&format!("commands.{}", name))
+    }
+
+    /// The setting a program name came from: a `commands.*` helper or
+    /// `maven.command`.
+    pub fn program_setting(&self, program: &str) -> Option<&'static str> {
+        self.values
+            .iter()
+            .find(|(key, setting)| {
+                (key.starts_with("commands.") || **key == "maven.command")
+                    && setting.value == program
+            })
+            .map(|(key, _)| *key)
+    }
+
+    pub fn settings(&self) -> impl Iterator<Item = (&str, &Setting)> {
+        self.values.iter().map(|(key, setting)| (*key, setting))
+    }
+}
+
+pub fn user_file() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("runapp").join(PROJECT_FILE))
+}
+
+/// `paths.mysql_dir` is overridden by `RUNAPP_PATHS_MYSQL_DIR`.
+pub fn env_var_name(key: &str) -> String {
+    format!("RUNAPP_{}", key.replace('.', "_").to_uppercase())
+}
+
+/// Parses a `--set KEY=VALUE` argument.
+pub fn parse_override(arg: &str) -> Result<(String, String)> {
+    match arg.split_once('=') {
+        Some((key, value)) => Ok((key.trim().to_string(), value.trim().to_string())),
+        None => Err(invalid(format!(
+            "--set {}: expected KEY=VALUE, e.g. ports.http=8081",
+            arg
+        ))),
+    }
+}
+
+fn flatten(prefix: &str, value: &toml::Value, out: &mut Vec<(String, String)>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&key, value, out);
+            }
+        }
+        toml::Value::String(s) => out.push((prefix.to_string(), s.clone())),
+        other => out.push((prefix.to_string(), other.to_string())),
+    }
+}
+
+fn invalid(message: String) -> RunappError {
+    RunappError::Config(message)
+}
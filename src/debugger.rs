@@ -0,0 +1,28 @@
+use colored::*;
+use serde_json::{json, Value};
+
+/// A VS Code `launch.json` configuration that attaches the Java debugger to
+/// Tomcat's JPDA port.
+pub fn launch_configuration(register_name: &str, port: u16) -> Value {
+    json!({
+        "type": "java",
+        "name": format!("Attach to Tomcat ({})", register_name),
+        "request": "attach",
+        "hostName": "localhost",
+        "port": port,
+    })
+}
+
+/// Prints what to paste into VS Code and IntelliJ to attach to Tomcat.
+pub fn print_attach_configs(register_name: &str, port: u16) {
+    println!("{}", "\nAttach a debugger:".bright_blue());
+    println!("  VS Code, in .vscode/launch.json under \"configurations\":");
+    let snippet = serde_json::to_string_pretty(&launch_configuration(register_name, port))
+        .unwrap_or_default();
+    for line in snippet.lines() {
+        println!("    {}", line);
+    }
+    println!("  IntelliJ: Run > Edit Configurations > + > Remote JVM Debug, with");
+    println!("    Debugger mode: Attach to remote JVM");
+    println!("    Host: localhost   Port: {}", port);
+}
@@ -0,0 +1,218 @@
+use colored::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{Result, RunappError};
+use crate::health::{Probe, Readiness};
+use crate::tomcat::read_new_lines;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const FINISHED: &str = "Deployment of web application archive [";
+const FAILED: &str = "Error deploying web application archive [";
+/// Lines kept from one SEVERE entry, stack trace included.
+const SEVERE_LINES: usize = 15;
+/// SEVERE entries shown at most, oldest first.
+const SEVERE_ENTRIES: usize = 3;
+
+/// How far Tomcat's logs were written before a deployment, so only what the
+/// deployment logged is looked at afterwards.
+pub struct LogMark {
+    dir: PathBuf,
+    offsets: Vec<(PathBuf, u64)>,
+}
+
+impl LogMark {
+    pub fn new(logs_dir: &Path) -> Self {
+        let offsets = watched_logs(logs_dir)
+            .into_iter()
+            .map(|path| {
+                let len = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                (path, len)
+            })
+            .collect();
+        LogMark {
+            dir: logs_dir.to_path_buf(),
+            offsets,
+        }
+    }
+
+    /// Lines written since the mark, per log file. Logs created since then
+    /// (such as a new day's `localhost.<date>.log`) are read from the start.
+    fn new_lines(&self) -> Result<Vec<(String, Vec<String>)>> {
+        let mut logs = Vec::new();
+        for path in watched_logs(&self.dir) {
+            let offset = self
+                .offsets
+                .iter()
+                .find(|(marked, _)| *marked == path)
+                .map(|(_, offset)| *offset)
+                .unwrap_or(0);
+            let mut lines = Vec::new();
+            read_new_lines(&path, offset, &mut lines)?;
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            logs.push((name, lines));
+        }
+        Ok(logs)
+    }
+}
+
+/// catalina.out, where Tomcat reports deployments, and the localhost logs,
+/// where exceptions thrown while starting a webapp end up.
+fn watched_logs(dir: &Path) -> Vec<PathBuf> {
+    let mut logs = vec![dir.join("catalina.out")];
+    if let Ok(entries) = fs::read_dir(dir) {
+        let mut localhost: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("localhost.") && name.ends_with(".log"))
+            })
+            .collect();
+        localhost.sort();
+        logs.extend(localhost);
+    }
+    logs
+}
+
+/// What Tomcat's logs say about deploying one WAR.
+#[derive(Default)]
+struct Report {
+    /// How long the deployment took, e.g. `2,345 ms`.
+    finished: Option<String>,
+    /// The `Error deploying` line for the WAR, if any.
+    failed: Option<String>,
+    /// SEVERE entries with their stack traces, each prefixed by its log.
+    severe: Vec<Vec<String>>,
+}
+
+impl Report {
+    fn scan(logs: &[(String, Vec<String>)], war_file: &str) -> Self {
+        let ours = format!("/{}]", war_file);
+        let mut report = Report::default();
+        for (name, lines) in logs {
+            let mut i = 0;
+            while i < lines.len() {
+                let line = &lines[i];
+                if line.contains(FINISHED) && line.contains(&ours) {
+                    report.finished = line
+                        .split_once("has finished in [")
+                        .and_then(|(_, rest)| rest.split_once(']'))
+                        .map(|(time, unit)| format!("{}{}", time, unit.trim_end()));
+                } else if line.contains(FAILED) && line.contains(&ours) {
+                    report.failed = Some(line.clone());
+                }
+
+                if !is_severe(line) {
+                    i += 1;
+                    continue;
+                }
+                let mut entry = vec![format!("[{}] {}", name, line)];
+                i += 1;
+                while i < lines.len() && is_continuation(&lines[i]) {
+                    if entry.len() < SEVERE_LINES {
+                        entry.push(format!("[{}] {}", name, lines[i]));
+                    }
+                    i += 1;
+                }
+                report.severe.push(entry);
+            }
+        }
+        report
+    }
+
+    fn excerpt(&self) -> String {
+        self.severe
+            .iter()
+            .take(SEVERE_ENTRIES)
+            .map(|entry| entry.join("\n"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn is_severe(line: &str) -> bool {
+    line.contains(" SEVERE ") || line.starts_with("SEVERE:")
+}
+
+/// Tomcat starts every log record with its date, so anything else (a stack
+/// trace, `Caused by:`, the rest of a long message) belongs to the last one.
+fn is_continuation(line: &str) -> bool {
+    !line.starts_with(|c: char| c.is_ascii_digit()) && !line.starts_with("SEVERE:")
+}
+
+/// Waits until Tomcat logs, after `mark`, that it finished or failed
+/// deploying `war_file`. Used when autodeploy picks up a WAR replaced while
+/// Tomcat is running, which it does on its next background check.
+pub fn wait_deployed(mark: &LogMark, war_file: &str, timeout: Duration) -> Result<()> {
+    println!(
+        "{}",
+        format!("Waiting for Tomcat to redeploy {}...", war_file).yellow()
+    );
+    let start = Instant::now();
+    loop {
+        let report = Report::scan(&mark.new_lines()?, war_file);
+        if report.finished.is_some() || report.failed.is_some() {
+            return Ok(());
+        }
+        if start.elapsed() > timeout {
+            return Err(RunappError::DeployFailed(format!(
+                "Tomcat did not pick up the new {} within {}s; is autoDeploy enabled on the Host in server.xml?",
+                war_file,
+                timeout.as_secs()
+            )));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Checks that `war_file` deployed: reads what Tomcat logged since `mark`,
+/// then polls `url` until the webapp answers. Fails with `DeployFailed` and
+/// the SEVERE entries from the logs if either says it did not.
+pub fn verify(mark: &LogMark, war_file: &str, url: &str, readiness: &Readiness) -> Result<()> {
+    let report = Report::scan(&mark.new_lines()?, war_file);
+    let excerpt = report.excerpt();
+    if let Some(failed) = &report.failed {
+        return Err(RunappError::DeployFailed(format!(
+            "{}\n{}",
+            failed, excerpt
+        )));
+    }
+    match &report.finished {
+        Some(time) => println!("{}", format!("Deployed {} in {}", war_file, time).green()),
+        None => println!(
+            "{}",
+            format!("Tomcat did not log a deployment of {}", war_file).yellow()
+        ),
+    }
+
+    if let Err(e) = readiness.wait_ready("webapp", &Probe::Http(url.to_string())) {
+        return Err(RunappError::DeployFailed(if excerpt.is_empty() {
+            format!("{} (nothing SEVERE in the Tomcat logs)", e)
+        } else {
+            format!("{}\n{}", e, excerpt)
+        }));
+    }
+
+    if !report.severe.is_empty() {
+        println!(
+            "{}",
+            format!(
+                "The webapp answers, but Tomcat logged {} SEVERE entr{}:",
+                report.severe.len(),
+                if report.severe.len() == 1 { "y" } else { "ies" }
+            )
+            .yellow()
+        );
+        for line in excerpt.lines() {
+            println!("  {}", line.dimmed());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::error::Result;
+use crate::executor::Executor;
+
+/// Where the MySQL directory is mounted inside the container, so the app can
+/// reach the socket in it.
+const MYSQL_MOUNT: &str = "/run/runapp-mysql";
+
+/// How the container reaches the host's MySQL and exposes its HTTP port.
+pub enum Network {
+    /// Docker's default network: the MySQL directory is mounted for its
+    /// socket and `host_port` is mapped to `container_port`.
+    Bridge {
+        host_port: u16,
+        container_port: u16,
+        mysql_dir: PathBuf,
+    },
+    /// The host's network: MySQL is reached over TCP on localhost and the
+    /// app listens on the host directly.
+    Host,
+}
+
+/// A container runapp starts from the image it built.
+pub struct Container<'a> {
+    pub name: &'a str,
+    pub image: &'a str,
+    pub network: Network,
+    pub envs: Vec<(&'static str, String)>,
+}
+
+impl Container<'_> {
+    /// Starts the container detached, replacing one left from an earlier run.
+    pub fn run(&self, exec: &Executor) -> Result<()> {
+        remove(exec, self.name)?;
+
+        let mut command = Command::new("docker");
+        command.args(["run", "--detach", "--name", self.name]);
+        let mut envs = self.envs.clone();
+        match &self.network {
+            Network::Bridge {
+                host_port,
+                container_port,
+                mysql_dir,
+            } => {
+                command
+                    .arg("--publish")
+                    .arg(format!("{}:{}", host_port, container_port))
+                    .arg("--volume")
+                    .arg(format!("{}:{}", mysql_dir.display(), MYSQL_MOUNT));
+                envs.retain(|(key, _)| *key != "MYSQL_UNIX_PORT");
+                envs.push(("MYSQL_UNIX_PORT", format!("{}/socket", MYSQL_MOUNT)));
+            }
+            Network::Host => {
+                command.args(["--network", "host"]);
+                envs.retain(|(key, _)| *key != "MYSQL_UNIX_PORT");
+                envs.push(("MYSQL_HOST", "127.0.0.1".to_string()));
+            }
+        }
+        for (key, value) in envs {
+            command.arg("--env").arg(format!("{}={}", key, value));
+        }
+        command.arg(self.image);
+        exec.run(&mut command)
+    }
+}
+
+/// The ID of a local image, e.g. `sha256:4f1c...`.
+pub fn image_id(exec: &Executor, image: &str) -> Result<Option<String>> {
+    let output = exec.probe(
+        Command::new("docker")
+            .args(["image", "inspect", "--format", "{{.Id}}", image])
+            .stderr(Stdio::null()),
+    )?;
+    let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok((output.status.success() && !id.is_empty()).then_some(id))
+}
+
+/// Whether a container named `name` is running; `None` if Docker cannot be
+/// asked.
+pub fn is_running(exec: &Executor, name: &str) -> Option<bool> {
+    exec.probe(
+        Command::new("docker")
+            .args(["ps", "--quiet", "--filter"])
+            .arg(format!("name=^{}$", name))
+            .stderr(Stdio::null()),
+    )
+    .ok()
+    .filter(|output| output.status.success())
+    .map(|output| !output.stdout.is_empty())
+}
+
+/// Stops and removes the container named `name`. Returns whether there was
+/// one.
+pub fn remove(exec: &Executor, name: &str) -> Result<bool> {
+    let status = exec.status(
+        Command::new("docker")
+            .args(["rm", "--force", name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null()),
+    )?;
+    Ok(status.success())
+}
+
+/// The first 12 hex digits of an image or container ID, as `docker` shows it.
+pub fn short_id(id: &str) -> &str {
+    let id = id.strip_prefix("sha256:").unwrap_or(id);
+    &id[..id.len().min(12)]
+}
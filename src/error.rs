@@ -0,0 +1,299 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::policy::Refusal;
+
+pub type Result<T> = std::result::Result<T, RunappError>;
+
+/// Everything that can make runapp give up. Each variant has its own process
+/// exit code and a hint telling the user how to get going again.
+#[derive(Debug)]
+pub enum RunappError {
+    /// A helper binary could not be started because it is not on `PATH`.
+    CommandNotFound {
+        name: String,
+        /// The setting that names the program, e.g. `commands.start_mysql`,
+        /// if it came from one.
+        setting: Option<&'static str>,
+    },
+    /// A command ran but exited unsuccessfully.
+    CommandFailed {
+        name: String,
+        code: Option<i32>,
+        stderr: String,
+    },
+    /// A required environment variable is unset or empty.
+    MissingEnv(&'static str),
+    /// Something is already listening on a port runapp needs.
+    PortInUse {
+        port: u16,
+        /// The setting the port came from, e.g. `ports.http`.
+        setting: &'static str,
+        /// Whether changing `setting` moves the component to another port.
+        /// Tomcat's HTTP port is fixed by its server.xml.
+        movable: bool,
+        pid: Option<u32>,
+        command: Option<String>,
+    },
+    DatabaseInit(String),
+    /// Maven failed; carries a one-line summary of the build errors.
+    MavenBuild(String),
+    Config(String),
+    Register(String),
+    DeletionRefused(Refusal),
+    /// A component did not become ready (or stop) in time.
+    NotReady(String),
+    InvalidPipeline(String),
+    /// Tomcat logged a startup or deployment failure; carries the log excerpt.
+    TomcatStart(String),
+    /// Tomcat started, but the webapp did not deploy or does not answer;
+    /// carries the SEVERE entries from Tomcat's logs.
+    DeployFailed(String),
+    /// A component that has to be running already, such as Tomcat for a
+    /// redeploy, is not.
+    NotRunning(&'static str),
+    StepFailed {
+        step: &'static str,
+        source: Box<RunappError>,
+    },
+    Io(io::Error),
+}
+
+impl RunappError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RunappError::Io(_) => 1,
+            RunappError::CommandNotFound { .. } => 3,
+            RunappError::CommandFailed { .. } => 4,
+            RunappError::MissingEnv(_) => 5,
+            RunappError::PortInUse { .. } => 6,
+            RunappError::DatabaseInit(_) => 7,
+            RunappError::MavenBuild(_) => 8,
+            RunappError::Config(_) => 9,
+            RunappError::Register(_) => 10,
+            RunappError::DeletionRefused(_) => 11,
+            RunappError::NotReady(_) => 12,
+            RunappError::InvalidPipeline(_) => 13,
+            RunappError::TomcatStart(_) => 14,
+            RunappError::DeployFailed(_) => 15,
+            RunappError::NotRunning(_) => 16,
+            RunappError::StepFailed { source, .. } => source.exit_code(),
+        }
+    }
+
+    pub fn hint(&self) -> Option<String> {
+        let hint = match self {
+            RunappError::CommandNotFound {
+                name,
+                setting: Some(setting),
+            } => format!(
+                "Make sure `{}` is on PATH (e.g. inside the project's nix shell), or set {} in runapp.toml to the program to use.",
+                name, setting
+            ),
+            RunappError::CommandNotFound { name, setting: None } => format!(
+                "Install `{}` or make sure it is on PATH (e.g. inside the project's nix shell).",
+                name
+            ),
+            RunappError::CommandFailed { name, .. } => {
+                format!("Run `{}` by hand to see its full output.", name)
+            }
+            RunappError::MissingEnv(var) => {
+                format!("Export {} before running runapp.", var)
+            }
+            RunappError::PortInUse {
+                setting,
+                movable,
+                pid,
+                ..
+            } => {
+                let stop = match pid {
+                    Some(pid) => format!("Stop the process holding the port (kill {})", pid),
+                    None => "Stop the process holding the port".to_string(),
+                };
+                if *movable {
+                    format!("{} or use another one with --set {}=<port>.", stop, setting)
+                } else {
+                    format!(
+                        "{}, or change the connector's port in $CATALINA_HOME/conf/server.xml and set {} to the same port.",
+                        stop, setting
+                    )
+                }
+            }
+            RunappError::DatabaseInit(_) => {
+                "Run `runapp drop` to reset the local database, then try again.".to_string()
+            }
+            RunappError::MavenBuild(_) => {
+                "Fix the errors listed above and run runapp again.".to_string()
+            }
+            RunappError::Config(_) => {
+                "Check runapp.toml, or run `runapp config show` to see the merged settings.".to_string()
+            }
+            RunappError::Register(_) => {
+                "Make sure register.nix evaluates, or put a register.json or register.toml next to it.".to_string()
+            }
+            RunappError::DeletionRefused(_) => {
+                "Check CATALINA_HOME and the project directory; pass --force only if the deletion is intended.".to_string()
+            }
+            RunappError::NotReady(_) => {
+                "Check the component's logs, or raise health.timeout_secs for slow machines.".to_string()
+            }
+            RunappError::TomcatStart(_) => {
+                "See $CATALINA_HOME/logs/catalina.out and the webapp's logs for the full stack trace.".to_string()
+            }
+            RunappError::DeployFailed(_) => {
+                "See $CATALINA_HOME/logs/localhost.<date>.log for the webapp's startup errors, or set health.app_path if the context root does not answer.".to_string()
+            }
+            RunappError::NotRunning(_) => {
+                "Start it with `runapp local` or `runapp code` first.".to_string()
+            }
+            RunappError::StepFailed { source, .. } => return source.hint(),
+            RunappError::InvalidPipeline(_) | RunappError::Io(_) => return None,
+        };
+        Some(hint)
+    }
+
+    /// Fills in which setting named a program that could not be found, using
+    /// `lookup`, so the hint can point at it.
+    pub fn with_program_setting<F>(self, lookup: F) -> RunappError
+    where
+        F: Fn(&str) -> Option<&'static str>,
+    {
+        match self {
+            RunappError::CommandNotFound {
+                name,
+                setting: None,
+            } => RunappError::CommandNotFound {
+                setting: lookup(&name),
+                name,
+            },
+            RunappError::StepFailed { step, source } => RunappError::StepFailed {
+                step,
+                source: Box::new(source.with_program_setting(lookup)),
+            },
+            other => other,
+        }
+    }
+
+    /// Reports a failed helper command as a database setup failure.
+    pub fn into_database_init(self, what: &str) -> RunappError {
+        match self {
+            RunappError::CommandFailed { .. } => {
+                RunappError::DatabaseInit(format!("{}: {}", what, self))
+            }
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for RunappError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunappError::CommandNotFound { name, .. } => write!(f, "command not found: {}", name),
+            RunappError::CommandFailed { name, code, stderr } => {
+                match code {
+                    Some(code) => write!(f, "`{}` exited with code {}", name, code)?,
+                    None => write!(f, "`{}` was killed by a signal", name)?,
+                }
+                if !stderr.is_empty() {
+                    write!(f, ":\n{}", stderr)?;
+                }
+                Ok(())
+            }
+            RunappError::MissingEnv(var) => write!(f, "environment variable {} is not set", var),
+            RunappError::PortInUse {
+                port,
+                setting,
+                pid,
+                command,
+                ..
+            } => {
+                write!(f, "port {} ({}) is in use", port, setting)?;
+                if let Some(pid) = pid {
+                    write!(f, " by process {}", pid)?;
+                }
+                if let Some(command) = command {
+                    write!(f, ": {}", command)?;
+                }
+                Ok(())
+            }
+            RunappError::DatabaseInit(message) => write!(f, "database setup failed: {}", message),
+            RunappError::MavenBuild(summary) => write!(f, "Maven build failed: {}", summary),
+            RunappError::Config(message) => write!(f, "invalid configuration: {}", message),
+            RunappError::Register(message) => write!(f, "{}", message),
+            RunappError::DeletionRefused(refusal) => write!(f, "{}", refusal),
+            RunappError::NotReady(message) => write!(f, "{}", message),
+            RunappError::InvalidPipeline(message) => write!(f, "{}", message),
+            RunappError::TomcatStart(excerpt) => write!(f, "Tomcat failed to start:\n{}", excerpt),
+            RunappError::DeployFailed(message) => {
+                write!(f, "the webapp did not deploy: {}", message)
+            }
+            RunappError::NotRunning(component) => write!(f, "{} is not running", component),
+            RunappError::StepFailed { step, source } => {
+                write!(f, "step '{}' failed: {}", step, source)
+            }
+            RunappError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for RunappError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RunappError::StepFailed { source, .. } => Some(source.as_ref()),
+            RunappError::DeletionRefused(refusal) => Some(refusal),
+            RunappError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RunappError {
+    fn from(e: io::Error) -> RunappError {
+        RunappError::Io(e)
+    }
+}
+
+impl From<Refusal> for RunappError {
+    fn from(refusal: Refusal) -> RunappError {
+        RunappError::DeletionRefused(refusal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn not_found(name: &str) -> RunappError {
+        RunappError::StepFailed {
+            step: "start-database",
+            source: Box::new(RunappError::CommandNotFound {
+                name: name.to_string(),
+                setting: None,
+            }),
+        }
+    }
+
+    fn lookup(program: &str) -> Option<&'static str> {
+        (program == "start_mysql").then_some("commands.start_mysql")
+    }
+
+    #[test]
+    fn points_helpers_at_their_setting() {
+        let hint = not_found("start_mysql")
+            .with_program_setting(lookup)
+            .hint()
+            .unwrap();
+        assert!(hint.contains("set commands.start_mysql in runapp.toml"));
+    }
+
+    #[test]
+    fn tells_to_install_other_programs() {
+        let hint = not_found("docker")
+            .with_program_setting(lookup)
+            .hint()
+            .unwrap();
+        assert!(hint.starts_with("Install `docker`"));
+        assert!(!hint.contains("runapp.toml"));
+    }
+}
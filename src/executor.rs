@@ -0,0 +1,336 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::error::{Result, RunappError};
+use crate::policy::DeletionPolicy;
+
+/// How many lines of a failed command's stderr end up in the error.
+const STDERR_TAIL: usize = 10;
+
+/// A side effect runapp performs on the machine.
+#[derive(Clone, Debug)]
+pub enum Action {
+    Run {
+        program: String,
+        args: Vec<String>,
+        envs: Vec<(String, String)>,
+        log: Option<PathBuf>,
+    },
+    Remove(PathBuf),
+    Copy(PathBuf, PathBuf),
+    Rename(PathBuf, PathBuf),
+    CreateDir(PathBuf),
+    CreateFile(PathBuf),
+    Write(PathBuf),
+}
+
+impl Action {
+    fn run(command: &Command, log: Option<&Path>) -> Action {
+        Action::Run {
+            program: command.get_program().to_string_lossy().into_owned(),
+            args: command
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+            envs: command
+                .get_envs()
+                .filter_map(|(key, value)| {
+                    value.map(|value| {
+                        (
+                            key.to_string_lossy().into_owned(),
+                            value.to_string_lossy().into_owned(),
+                        )
+                    })
+                })
+                .collect(),
+            log: log.map(Path::to_path_buf),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Run {
+                program,
+                args,
+                envs,
+                log,
+            } => {
+                write!(f, "run    ")?;
+                for (key, value) in envs {
+                    write!(f, "{}={} ", key, quote(value))?;
+                }
+                write!(f, "{}", quote(program))?;
+                for arg in args {
+                    write!(f, " {}", quote(arg))?;
+                }
+                if let Some(log) = log {
+                    write!(f, " > {} 2>&1", log.display())?;
+                }
+                Ok(())
+            }
+            Action::Remove(path) => write!(f, "remove {}", path.display()),
+            Action::Copy(from, to) => write!(f, "copy   {} -> {}", from.display(), to.display()),
+            Action::Rename(from, to) => write!(f, "move   {} -> {}", from.display(), to.display()),
+            Action::CreateDir(path) => write!(f, "mkdir  {}", path.display()),
+            Action::CreateFile(path) => write!(f, "create {}", path.display()),
+            Action::Write(path) => write!(f, "write  {}", path.display()),
+        }
+    }
+}
+
+fn quote(value: &str) -> String {
+    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '\'' || c == '|') {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Every process runapp spawns and every file it touches goes through here.
+/// In dry-run mode nothing is executed; the actions are only recorded, and
+/// commands report success with empty output.
+pub struct Executor {
+    dry_run: bool,
+    policy: DeletionPolicy,
+    actions: Mutex<Vec<Action>>,
+}
+
+impl Executor {
+    pub fn new(dry_run: bool, policy: DeletionPolicy) -> Self {
+        Executor {
+            dry_run,
+            policy,
+            actions: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Actions recorded so far, in the order they were requested.
+    pub fn actions(&self) -> Vec<Action> {
+        self.actions.lock().unwrap().clone()
+    }
+
+    fn record(&self, action: Action) {
+        self.actions.lock().unwrap().push(action);
+    }
+
+    /// Runs `command` and returns its exit status, whatever it is. Use this
+    /// for commands that are allowed to fail, such as stopping something
+    /// that may not be running.
+    pub fn status(&self, command: &mut Command) -> Result<ExitStatus> {
+        self.record(Action::run(command, None));
+        if self.dry_run {
+            return Ok(ExitStatus::from_raw(0));
+        }
+        command
+            .status()
+            .map_err(|e| spawn_error(&command_name(command), e))
+    }
+
+    /// Runs `command` and fails with its last lines of stderr unless it exits
+    /// successfully. Stderr is still shown as it is written.
+    pub fn run(&self, command: &mut Command) -> Result<()> {
+        self.record(Action::run(command, None));
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let name = command_name(command);
+        let shell = is_shell(command);
+        let mut child = command
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| spawn_error(&name, e))?;
+        let mut tail = VecDeque::with_capacity(STDERR_TAIL);
+        if let Some(stderr) = child.stderr.take() {
+            for line in BufReader::new(stderr).lines() {
+                let line = line?;
+                eprintln!("{}", line);
+                if tail.len() == STDERR_TAIL {
+                    tail.pop_front();
+                }
+                tail.push_back(line);
+            }
+        }
+
+        let status = child.wait()?;
+        if status.success() {
+            return Ok(());
+        }
+        // sh reports a missing program with exit code 127. Other programs
+        // may use it for their own failures, e.g. `docker run` when the
+        // container's entrypoint is missing.
+        if shell && status.code() == Some(127) {
+            return Err(RunappError::CommandNotFound {
+                name,
+                setting: None,
+            });
+        }
+        Err(RunappError::CommandFailed {
+            name,
+            code: status.code(),
+            stderr: Vec::from(tail).join("\n"),
+        })
+    }
+
+    /// Runs `command` with stdout and stderr merged, writing every line to
+    /// `log` and handing it to `on_line` as it arrives.
+    pub fn stream_logged<F>(
+        &self,
+        command: &mut Command,
+        log: &Path,
+        mut on_line: F,
+    ) -> Result<ExitStatus>
+    where
+        F: FnMut(&str),
+    {
+        self.record(Action::run(command, Some(log)));
+        if self.dry_run {
+            return Ok(ExitStatus::from_raw(0));
+        }
+
+        let mut file = File::create(log)?;
+        let (reader, writer) = io::pipe()?;
+        let mut child = command
+            .stdout(writer.try_clone()?)
+            .stderr(writer)
+            .spawn()
+            .map_err(|e| spawn_error(&command_name(command), e))?;
+        // The command still holds the write end; drop it so reads see EOF
+        command.stdout(Stdio::null()).stderr(Stdio::null());
+
+        let mut reader = BufReader::new(reader);
+        let mut buffer = Vec::new();
+        while reader.read_until(b'\n', &mut buffer)? > 0 {
+            file.write_all(&buffer)?;
+            let line = String::from_utf8_lossy(&buffer);
+            on_line(line.trim_end_matches(['\n', '\r']));
+            buffer.clear();
+        }
+
+        Ok(child.wait()?)
+    }
+
+    /// Runs a read-only query such as `pgrep`. Probes are executed even in
+    /// dry-run mode so the recorded plan follows the real state of the machine.
+    pub fn probe(&self, command: &mut Command) -> Result<Output> {
+        command
+            .output()
+            .map_err(|e| spawn_error(&command_name(command), e))
+    }
+
+    /// Removes a file or a whole directory tree, provided the deletion policy
+    /// allows it.
+    pub fn remove(&self, path: &Path) -> Result<()> {
+        self.policy.check(path)?;
+        self.record(Action::Remove(path.to_path_buf()));
+        if self.dry_run {
+            return Ok(());
+        }
+        if fs::symlink_metadata(path)?.is_dir() {
+            fs::remove_dir_all(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.record(Action::Copy(from.to_path_buf(), to.to_path_buf()));
+        if self.dry_run {
+            return Ok(());
+        }
+        fs::copy(from, to)?;
+        Ok(())
+    }
+
+    /// Moves `from` to `to` in one step, replacing `to` if it exists.
+    pub fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.record(Action::Rename(from.to_path_buf(), to.to_path_buf()));
+        if self.dry_run {
+            return Ok(());
+        }
+        fs::rename(from, to)?;
+        Ok(())
+    }
+
+    pub fn create_dir_all(&self, path: &Path) -> Result<()> {
+        self.record(Action::CreateDir(path.to_path_buf()));
+        if self.dry_run {
+            return Ok(());
+        }
+        fs::create_dir_all(path)?;
+        Ok(())
+    }
+
+    pub fn create_file(&self, path: &Path) -> Result<()> {
+        self.record(Action::CreateFile(path.to_path_buf()));
+        if self.dry_run {
+            return Ok(());
+        }
+        File::create(path)?;
+        Ok(())
+    }
+
+    pub fn write(&self, path: &Path, contents: &str) -> Result<()> {
+        self.record(Action::Write(path.to_path_buf()));
+        if self.dry_run {
+            return Ok(());
+        }
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Waits for an external process to settle. Skipped in dry-run mode.
+    pub fn sleep(&self, duration: Duration) {
+        if !self.dry_run {
+            thread::sleep(duration);
+        }
+    }
+}
+
+/// The name to report for `command`: the program, or for `sh -c SCRIPT` the
+/// first word of the script.
+/// Whether `command` is `sh -c SCRIPT`.
+fn is_shell(command: &Command) -> bool {
+    command.get_program() == "sh" && command.get_args().next().is_some_and(|arg| arg == "-c")
+}
+
+fn command_name(command: &Command) -> String {
+    if is_shell(command) {
+        if let Some(word) = command.get_args().nth(1).and_then(|script| {
+            script
+                .to_string_lossy()
+                .split_whitespace()
+                .next()
+                .map(str::to_string)
+        }) {
+            return word;
+        }
+    }
+    command.get_program().to_string_lossy().into_owned()
+}
+
+fn spawn_error(name: &str, e: io::Error) -> RunappError {
+    if e.kind() == io::ErrorKind::NotFound {
+        RunappError::CommandNotFound {
+            name: name.to_string(),
+            setting: None,
+        }
+    } else {
+        RunappError::Io(e)
+    }
+}
@@ -0,0 +1,211 @@
+use colored::*;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::error::{Result, RunappError};
+
+/// A way of telling whether a component accepts connections.
+pub enum Probe {
+    /// Connect to a unix socket, e.g. MySQL's `mysql/socket`.
+    UnixSocket(PathBuf),
+    /// Open a TCP connection to `host:port`.
+    Tcp(String, u16),
+    /// Send a GET request; any response below 500 other than 404 counts as
+    /// ready.
+    Http(String),
+    /// Look for a process whose command line matches a pattern, for services
+    /// without an endpoint to poll.
+    Process(String),
+}
+
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Probe::UnixSocket(path) => write!(f, "socket {}", path.display()),
+            Probe::Tcp(host, port) => write!(f, "{}:{}", host, port),
+            Probe::Http(url) => write!(f, "{}", url),
+            Probe::Process(pattern) => write!(f, "a process matching '{}'", pattern),
+        }
+    }
+}
+
+impl Probe {
+    pub fn check(&self, timeout: Duration) -> io::Result<()> {
+        match self {
+            Probe::UnixSocket(path) => UnixStream::connect(path).map(|_| ()),
+            Probe::Tcp(host, port) => connect(host, *port, timeout).map(|_| ()),
+            Probe::Http(url) => http_get(url, timeout),
+            Probe::Process(pattern) => find_process(pattern),
+        }
+    }
+}
+
+/// Polls probes with exponential backoff until they succeed or time out.
+/// In dry-run mode nothing is started, so waiting always succeeds at once.
+pub struct Readiness {
+    timeout: Duration,
+    initial_delay: Duration,
+    max_delay: Duration,
+    dry_run: bool,
+}
+
+impl Readiness {
+    pub fn new(config: &Config, dry_run: bool) -> Self {
+        Readiness {
+            timeout: Duration::from_secs(config.number("health.timeout_secs")),
+            initial_delay: Duration::from_millis(config.number("health.backoff_initial_ms")),
+            max_delay: Duration::from_millis(config.number("health.backoff_max_ms")),
+            dry_run,
+        }
+    }
+
+    /// Waits until `name` answers on `probe`.
+    pub fn wait_ready(&self, name: &str, probe: &Probe) -> Result<()> {
+        self.wait(name, probe, true)
+    }
+
+    /// Waits until `name` stops answering on `probe`, e.g. after a shutdown.
+    pub fn wait_gone(&self, name: &str, probe: &Probe) -> Result<()> {
+        self.wait(name, probe, false)
+    }
+
+    fn wait(&self, name: &str, probe: &Probe, ready: bool) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let state = if ready { "ready" } else { "stopped" };
+        println!(
+            "{}",
+            format!(
+                "Waiting for {} on {} (timeout {}s)...",
+                name,
+                probe,
+                self.timeout.as_secs()
+            )
+            .yellow()
+        );
+
+        let start = Instant::now();
+        let deadline = start + self.timeout;
+        let mut delay = self.initial_delay;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let result =
+                probe.check(remaining.clamp(Duration::from_millis(100), Duration::from_secs(2)));
+            if result.is_ok() == ready {
+                println!(
+                    "{}",
+                    format!(
+                        "{} is {} after {:.1}s",
+                        name,
+                        state,
+                        start.elapsed().as_secs_f64()
+                    )
+                    .green()
+                );
+                return Ok(());
+            }
+
+            if Instant::now() + delay > deadline {
+                let detail = match result {
+                    Err(e) => format!(" (last error: {})", e),
+                    Ok(()) => " (it is still answering)".to_string(),
+                };
+                return Err(RunappError::NotReady(format!(
+                    "{} was not {} on {} within {}s{}",
+                    name,
+                    state,
+                    probe,
+                    self.timeout.as_secs(),
+                    detail
+                )));
+            }
+            thread::sleep(delay);
+            delay = (delay * 2).min(self.max_delay);
+        }
+    }
+}
+
+fn connect(host: &str, port: u16, timeout: Duration) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "no address found");
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+fn find_process(pattern: &str) -> io::Result<()> {
+    let found = Command::new("pgrep")
+        .arg("-f")
+        .arg(pattern)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?
+        .success();
+    if found {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::NotFound, "no such process"))
+    }
+}
+
+fn http_get(url: &str, timeout: Duration) -> io::Result<()> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("only http:// URLs can be probed, got {}", url),
+        )
+    })?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid port in {}", url),
+                )
+            })?,
+        ),
+        None => (authority, 80),
+    };
+
+    let mut stream = connect(host, port, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    write!(
+        stream,
+        "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, authority
+    )?;
+
+    let mut buffer = [0; 64];
+    let read = stream.read(&mut buffer)?;
+    let status_line = String::from_utf8_lossy(&buffer[..read]);
+    let status: u16 = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not an HTTP response"))?;
+
+    // A 404 is Tomcat answering for a context that is not deployed (yet)
+    if status < 500 && status != 404 {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("HTTP {}", status)))
+    }
+}
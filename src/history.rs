@@ -0,0 +1,249 @@
+use chrono::{DateTime, Local};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::error::{Result, RunappError};
+use crate::format_duration;
+use crate::pipeline::{Outcome, Timing};
+
+const FILE: &str = "history.jsonl";
+/// Runs `runapp history` lists when `--last` is not given.
+const DEFAULT_LAST: usize = 10;
+
+/// One line of the history file: a finished run of a mode.
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    /// When the run started, in RFC 3339.
+    pub started: String,
+    pub mode: String,
+    pub register: String,
+    /// The short commit the project was at, if it is a git checkout.
+    pub commit: Option<String>,
+    pub duration_ms: u64,
+    pub steps: Vec<StepEntry>,
+    pub exit_code: i32,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StepEntry {
+    pub name: String,
+    pub duration_ms: u64,
+    /// `ok`, `failed` or `skipped`.
+    pub status: String,
+}
+
+impl Entry {
+    pub fn new(
+        started: DateTime<Local>,
+        mode: &str,
+        register: &str,
+        duration: Duration,
+        timings: &[Timing],
+        error: Option<&RunappError>,
+    ) -> Self {
+        Entry {
+            started: started.to_rfc3339(),
+            mode: mode.to_string(),
+            register: register.to_string(),
+            commit: git_commit(),
+            duration_ms: duration.as_millis() as u64,
+            steps: timings
+                .iter()
+                .map(|timing| StepEntry {
+                    name: timing.name.to_string(),
+                    duration_ms: timing.duration.as_millis() as u64,
+                    status: match timing.outcome {
+                        Outcome::Ok => "ok",
+                        Outcome::Failed => "failed",
+                        Outcome::Skipped => "skipped",
+                    }
+                    .to_string(),
+                })
+                .collect(),
+            exit_code: error.map_or(0, RunappError::exit_code),
+            error: error.map(|e| e.to_string()),
+        }
+    }
+}
+
+/// The history file, `runapp/history.jsonl` in the user's data directory
+/// (e.g. `~/.local/share` on Linux).
+pub fn path() -> Result<PathBuf> {
+    dirs::data_dir()
+        .map(|dir| dir.join("runapp").join(FILE))
+        .ok_or(RunappError::MissingEnv("HOME"))
+}
+
+/// Appends `entry` to the history file.
+pub fn append(entry: &Entry) -> Result<()> {
+    let path = path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let line = serde_json::to_string(entry).map_err(std::io::Error::other)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+/// Every recorded run, oldest first. Lines that cannot be read, e.g. one cut
+/// short by a crash, are skipped.
+pub fn load() -> Result<Vec<Entry>> {
+    let contents = match fs::read_to_string(path()?) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Prints the last `last` runs, or the time per step over them with `stats`.
+pub fn show(last: Option<usize>, stats: bool) -> Result<()> {
+    let entries = load()?;
+    if entries.is_empty() {
+        println!("No runs recorded yet in {}.", path()?.display());
+        return Ok(());
+    }
+    if stats {
+        let skip = last.map_or(0, |last| entries.len().saturating_sub(last));
+        show_stats(&entries[skip..]);
+    } else {
+        let last = last.unwrap_or(DEFAULT_LAST);
+        show_runs(&entries[entries.len().saturating_sub(last)..]);
+    }
+    Ok(())
+}
+
+fn show_runs(entries: &[Entry]) {
+    println!(
+        "{}",
+        format!(
+            "{:<16}  {:<14}  {:<16}  {:<9}  {:>12}  Result",
+            "Started", "Mode", "Register", "Commit", "Duration"
+        )
+        .bright_blue()
+    );
+    for entry in entries {
+        let started = DateTime::parse_from_rfc3339(&entry.started)
+            .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|_| entry.started.clone());
+        let result = match &entry.error {
+            None => "ok".green(),
+            // The first line only, so a multi-line error keeps the table readable
+            Some(error) => format!(
+                "exit {}: {}",
+                entry.exit_code,
+                error.lines().next().unwrap_or_default()
+            )
+            .red(),
+        };
+        println!(
+            "{:<16}  {:<14}  {:<16}  {:<9}  {:>12}  {}",
+            started,
+            entry.mode,
+            if entry.register.is_empty() {
+                "-"
+            } else {
+                &entry.register
+            },
+            entry.commit.as_deref().unwrap_or("-"),
+            format_duration(Duration::from_millis(entry.duration_ms)),
+            result
+        );
+    }
+}
+
+/// Average and 95th percentile per step for each mode, counting only the runs
+/// in which the step succeeded, so failed and cut-short runs do not skew them.
+/// Modes run different steps and take very different times, so they are
+/// never pooled.
+fn show_stats(entries: &[Entry]) {
+    let mut modes: Vec<(&str, Vec<&Entry>)> = Vec::new();
+    for entry in entries {
+        match modes.iter_mut().find(|(mode, _)| *mode == entry.mode) {
+            Some((_, runs)) => runs.push(entry),
+            None => modes.push((&entry.mode, vec![entry])),
+        }
+    }
+
+    println!(
+        "{}",
+        format!(
+            "Time per step over the last {} run{}:",
+            entries.len(),
+            if entries.len() == 1 { "" } else { "s" }
+        )
+        .bright_blue()
+    );
+    for (mode, runs) in modes {
+        show_mode_stats(mode, &runs);
+    }
+}
+
+fn show_mode_stats(mode: &str, entries: &[&Entry]) {
+    let mut steps: Vec<(&str, Vec<u64>)> = Vec::new();
+    for entry in entries {
+        for step in entry.steps.iter().filter(|step| step.status == "ok") {
+            match steps.iter_mut().find(|(name, _)| *name == step.name) {
+                Some((_, durations)) => durations.push(step.duration_ms),
+                None => steps.push((&step.name, vec![step.duration_ms])),
+            }
+        }
+    }
+    let mut totals: Vec<u64> = entries
+        .iter()
+        .filter(|entry| entry.error.is_none())
+        .map(|entry| entry.duration_ms)
+        .collect();
+
+    println!(
+        "\n  {} ({} run{})",
+        mode.bold(),
+        entries.len(),
+        if entries.len() == 1 { "" } else { "s" }
+    );
+    println!(
+        "  {:<20}  {:>5}  {:>12}  {:>12}",
+        "Step", "Runs", "Average", "p95"
+    );
+    for (name, durations) in steps.iter_mut() {
+        print_stats(name, durations);
+    }
+    if !totals.is_empty() {
+        print_stats("total", &mut totals);
+    }
+}
+
+fn print_stats(name: &str, durations: &mut [u64]) {
+    durations.sort_unstable();
+    let average = durations.iter().sum::<u64>() / durations.len() as u64;
+    // Nearest rank: the smallest duration at least 95% of the runs fit in
+    let rank = (durations.len() * 95).div_ceil(100);
+    let p95 = durations[rank.max(1) - 1];
+    println!(
+        "  {:<20}  {:>5}  {:>12}  {:>12}",
+        name,
+        durations.len(),
+        format_duration(Duration::from_millis(average)),
+        format_duration(Duration::from_millis(p95))
+    );
+}
+
+fn git_commit() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !commit.is_empty()).then_some(commit)
+}
@@ -0,0 +1,513 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeDelta};
+use colored::*;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+use crate::tomcat::read_new_lines;
+use crate::{catalina_home, Context};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long a followed command's stderr is held back: what it writes before
+/// failing this early is its own error rather than log content.
+const STARTUP_GRACE: Duration = Duration::from_secs(1);
+/// Lines shown per component when `--since` is not given, like `tail`.
+const DEFAULT_LINES: usize = 100;
+/// Timestamp layouts that start log lines: Tomcat's, and the ISO-like ones
+/// used by MySQL, Spring Boot and log4j.
+const FORMATS: &[&str] = &[
+    "%d-%b-%Y %H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S,%3f",
+];
+
+/// A source of log lines runapp knows about.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Component {
+    Maven,
+    Tomcat,
+    Mysql,
+    AuthServer,
+    Pdp,
+}
+
+impl Component {
+    pub const ALL: &'static [Component] = &[
+        Component::Maven,
+        Component::Tomcat,
+        Component::Mysql,
+        Component::AuthServer,
+        Component::Pdp,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Component::Maven => "maven",
+            Component::Tomcat => "tomcat",
+            Component::Mysql => "mysql",
+            Component::AuthServer => "auth-server",
+            Component::Pdp => "pdp",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Component> {
+        Component::ALL
+            .iter()
+            .copied()
+            .find(|component| component.name() == name)
+    }
+
+    fn prefix(self) -> ColoredString {
+        let name = format!("{:<11}", self.name());
+        match self {
+            Component::Maven => name.blue(),
+            Component::Tomcat => name.yellow(),
+            Component::Mysql => name.cyan(),
+            Component::AuthServer => name.magenta(),
+            Component::Pdp => name.green(),
+        }
+    }
+}
+
+pub struct Options {
+    pub components: Vec<Component>,
+    pub follow: bool,
+    pub since: Option<Duration>,
+    pub grep: Option<String>,
+}
+
+/// Parses `--since` values such as `30s`, `10m`, `2h` or `1d`.
+pub fn parse_since(value: &str) -> std::result::Result<Duration, String> {
+    let invalid = || format!("'{}' is not a duration like 30s, 10m, 2h or 1d", value);
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let amount: u64 = value[..split].parse().map_err(|_| invalid())?;
+    let unit = match &value[split..] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    let secs = amount.checked_mul(unit).ok_or_else(invalid)?;
+    Ok(Duration::from_secs(secs))
+}
+
+/// Where a component's lines come from.
+enum Source {
+    File(PathBuf),
+    Command(Command),
+}
+
+struct Line {
+    component: Component,
+    /// When the line was logged: its own timestamp, or the last one before
+    /// it in the same source. Lines before the first timestamp take that one,
+    /// or when the source has none, the time it was last written.
+    time: Option<NaiveDateTime>,
+    /// Whether `time` was read from this line.
+    stamped: bool,
+    text: String,
+}
+
+/// Prints the logs of `options.components` (all of them if empty) as one
+/// stream. Without `follow` the lines are merged in time order; with it,
+/// new lines are printed as they are written until runapp is interrupted.
+pub fn show(ctx: &Context, options: &Options) -> Result<()> {
+    let components = if options.components.is_empty() {
+        Component::ALL
+    } else {
+        &options.components[..]
+    };
+    // A window reaching back further than chrono can count covers every line
+    let cutoff = options.since.and_then(|since| {
+        Local::now()
+            .naive_local()
+            .checked_sub_signed(TimeDelta::from_std(since).ok()?)
+    });
+
+    let mut backlog = Vec::new();
+    let mut tails = Vec::new();
+    let mut commands = Vec::new();
+    for &component in components {
+        match source(ctx, component, options) {
+            Ok(Source::File(path)) => {
+                let contents = fs::read(&path)?;
+                let modified = fs::metadata(&path)?
+                    .modified()
+                    .ok()
+                    .map(|time| DateTime::<Local>::from(time).naive_local());
+                let text = String::from_utf8_lossy(&contents);
+                let mut lines = parse_lines(component, text.lines());
+                place_untimed(&mut lines, modified);
+                backlog.extend(recent(lines, cutoff));
+                tails.push((component, path, contents.len() as u64));
+            }
+            Ok(Source::Command(command)) => commands.push((component, command)),
+            Err(reason) => eprintln!(
+                "{}",
+                format!("{}: {}, skipping it", component.name(), reason).dimmed()
+            ),
+        }
+    }
+
+    if !options.follow {
+        for (component, mut command) in commands.drain(..) {
+            let ran = Local::now().naive_local();
+            let output = match command.output() {
+                Ok(output) => output,
+                Err(e) => {
+                    eprintln!(
+                        "{}",
+                        format!("{}: {}, skipping it", component.name(), e).dimmed()
+                    );
+                    continue;
+                }
+            };
+            let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+            if !output.status.success() {
+                report_failure(component, &command, output.status, stderr.lines());
+                continue;
+            }
+            // `docker logs` passes the container's stderr on as its own
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&stderr);
+            let mut lines = parse_lines(component, text.lines());
+            place_untimed(&mut lines, Some(ran));
+            backlog.extend(recent(lines, cutoff));
+        }
+    }
+
+    // Lines without a timestamp of their own keep their place in their source
+    backlog.sort_by_key(|line| line.time);
+    for line in &backlog {
+        print_line(line, options.grep.as_deref());
+    }
+    if !options.follow {
+        return Ok(());
+    }
+
+    let (tx, rx) = mpsc::channel();
+    for (component, path, offset) in tails {
+        let tx = tx.clone();
+        thread::spawn(move || tail_file(component, &path, offset, tx));
+    }
+    for (component, command) in commands {
+        stream_command(component, command, &tx);
+    }
+    drop(tx);
+    for line in rx {
+        print_line(&line, options.grep.as_deref());
+    }
+    Ok(())
+}
+
+fn source(
+    ctx: &Context,
+    component: Component,
+    options: &Options,
+) -> std::result::Result<Source, String> {
+    let file = |path: PathBuf| {
+        if path.is_file() {
+            Ok(Source::File(path))
+        } else {
+            Err(format!("{} does not exist", path.display()))
+        }
+    };
+    match component {
+        Component::Maven => file(PathBuf::from(ctx.config.compile_log())),
+        Component::Tomcat => {
+            let home = catalina_home().map_err(|e| e.to_string())?;
+            file(Path::new(&home).join("logs").join("catalina.out"))
+        }
+        Component::Mysql => {
+            let mysql_dir = ctx.mysql_dir().map_err(|e| e.to_string())?;
+            file(mysql_error_log(&mysql_dir.join("data"))?)
+        }
+        Component::AuthServer => Ok(Source::Command(Command::new(
+            ctx.config.command("auth_server_logs"),
+        ))),
+        Component::Pdp => {
+            let mut command = Command::new("docker");
+            command.args(["logs", "--timestamps"]);
+            if options.follow {
+                command.arg("--follow");
+            }
+            match options.since {
+                Some(since) => command.arg("--since").arg(format!("{}s", since.as_secs())),
+                None => command.arg("--tail").arg(DEFAULT_LINES.to_string()),
+            };
+            command.arg(ctx.config.get("services.pdp_container"));
+            Ok(Source::Command(command))
+        }
+    }
+}
+
+/// MySQL names its error log after the host (`<hostname>.err`) unless it is
+/// configured otherwise; the most recently written one is taken.
+fn mysql_error_log(data_dir: &Path) -> std::result::Result<PathBuf, String> {
+    let entries =
+        fs::read_dir(data_dir).map_err(|_| format!("{} does not exist", data_dir.display()))?;
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "err")
+                || path.file_name().is_some_and(|name| name == "error.log")
+        })
+        .max_by_key(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .ok_or_else(|| format!("no error log in {}", data_dir.display()))
+}
+
+fn parse_lines<'a>(component: Component, lines: impl Iterator<Item = &'a str>) -> Vec<Line> {
+    let mut last = None;
+    lines
+        .map(|line| {
+            let (time, text) = match timestamp(line) {
+                Some((time, rest)) => {
+                    last = Some(time);
+                    (Some(time), rest)
+                }
+                None => (last, line),
+            };
+            Line {
+                component,
+                time,
+                stamped: text.len() != line.len(),
+                text: text.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Gives the lines before a source's first timestamp that timestamp, or
+/// `fallback`, when the source was last written, if it has none at all. This
+/// keeps them in place among the other sources instead of ahead of them.
+fn place_untimed(lines: &mut [Line], fallback: Option<NaiveDateTime>) {
+    let first = lines.iter().find_map(|line| line.time).or(fallback);
+    for line in lines.iter_mut().take_while(|line| line.time.is_none()) {
+        line.time = first;
+    }
+}
+
+/// Keeps the lines logged after `cutoff`, or the last few without one.
+fn recent(lines: Vec<Line>, cutoff: Option<NaiveDateTime>) -> Vec<Line> {
+    let Some(cutoff) = cutoff else {
+        let skip = lines.len().saturating_sub(DEFAULT_LINES);
+        return lines.into_iter().skip(skip).collect();
+    };
+    lines
+        .into_iter()
+        .filter(|line| line.time.is_none_or(|time| time >= cutoff))
+        .collect()
+}
+
+/// Reads the timestamp a line starts with, returning it (in local time) and
+/// the rest of the line.
+fn timestamp(line: &str) -> Option<(NaiveDateTime, &str)> {
+    let mut words = line.splitn(3, ' ');
+    let first = words.next()?;
+    if let Ok(time) = DateTime::parse_from_rfc3339(first) {
+        let rest = line[first.len()..].trim_start();
+        return Some((time.with_timezone(&Local).naive_local(), rest));
+    }
+    let second = words.next()?;
+    let candidate = &line[..first.len() + 1 + second.len()];
+    FORMATS.iter().find_map(|format| {
+        NaiveDateTime::parse_from_str(candidate, format)
+            .ok()
+            .map(|time| (time, line[candidate.len()..].trim_start()))
+    })
+}
+
+fn print_line(line: &Line, grep: Option<&str>) {
+    if grep.is_some_and(|pattern| !line.text.contains(pattern)) {
+        return;
+    }
+    let time = match line.time {
+        Some(time) if line.stamped => time.format("%H:%M:%S%.3f").to_string(),
+        _ => " ".repeat(12),
+    };
+    println!(
+        "{} {} {} {}",
+        time.dimmed(),
+        line.component.prefix(),
+        "|".dimmed(),
+        line.text
+    );
+}
+
+fn tail_file(component: Component, path: &Path, mut offset: u64, tx: Sender<Line>) {
+    loop {
+        let mut lines = Vec::new();
+        match read_new_lines(path, offset, &mut lines) {
+            Ok(next) => offset = next,
+            Err(_) => return,
+        }
+        for line in parse_lines(component, lines.iter().map(String::as_str)) {
+            if tx.send(line).is_err() {
+                return;
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Reports that the command behind `component` failed, with what it wrote
+/// to stderr, apart from the log lines.
+fn report_failure<'a>(
+    component: Component,
+    command: &Command,
+    status: ExitStatus,
+    stderr: impl Iterator<Item = &'a str>,
+) {
+    let exit = match status.code() {
+        Some(code) => format!("exited with code {}", code),
+        None => "was killed".to_string(),
+    };
+    eprintln!(
+        "{}",
+        format!(
+            "{}: {} {}, skipping it",
+            component.name(),
+            command.get_program().to_string_lossy(),
+            exit
+        )
+        .dimmed()
+    );
+    for line in stderr.filter(|line| !line.trim().is_empty()) {
+        eprintln!("{}", format!("  {}", line).dimmed());
+    }
+}
+
+/// Streams the command's output as `component`'s lines. Its stderr is held
+/// back for `STARTUP_GRACE`, so that if it fails right away, e.g. because
+/// the Docker daemon is not running, that is reported as a failure rather
+/// than as log lines.
+fn stream_command(component: Component, mut command: Command, tx: &Sender<Line>) {
+    let mut child = match command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!(
+                "{}",
+                format!("{}: {}, skipping it", component.name(), e).dimmed()
+            );
+            return;
+        }
+    };
+    let started = Instant::now();
+    if let Some(stdout) = child.stdout.take() {
+        let tx = tx.clone();
+        thread::spawn(move || forward(component, stdout, |line| tx.send(line).is_ok()));
+    }
+    let held = Arc::new(Mutex::new(Some(Vec::new())));
+    let stderr = child.stderr.take().map(|stderr| {
+        let held = Arc::clone(&held);
+        let tx = tx.clone();
+        thread::spawn(move || {
+            forward(component, stderr, |line| {
+                match held.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+                    Some(held) => {
+                        held.push(line);
+                        true
+                    }
+                    None => tx.send(line).is_ok(),
+                }
+            })
+        })
+    });
+
+    let tx = tx.clone();
+    thread::spawn(move || {
+        let take_held = || held.lock().unwrap_or_else(|e| e.into_inner()).take();
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) => {
+                    if let Some(stderr) = stderr {
+                        let _ = stderr.join();
+                    }
+                    let early = take_held().unwrap_or_default();
+                    if status.success() {
+                        early.into_iter().for_each(|line| {
+                            let _ = tx.send(line);
+                        });
+                    } else {
+                        let stderr = early.iter().map(|line| line.text.as_str());
+                        report_failure(component, &command, status, stderr);
+                    }
+                    return;
+                }
+                Ok(None) if started.elapsed() >= STARTUP_GRACE => {
+                    for line in take_held().unwrap_or_default() {
+                        let _ = tx.send(line);
+                    }
+                }
+                Ok(None) => {}
+                Err(_) => return,
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+/// Hands each line of `output` to `send` until it returns false.
+fn forward(component: Component, output: impl Read, mut send: impl FnMut(Line) -> bool) {
+    for line in BufReader::new(output).lines() {
+        let Ok(line) = line else { return };
+        for line in parse_lines(component, std::iter::once(line.as_str())) {
+            if !send(line) {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_since_durations() {
+        assert_eq!(parse_since("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_since("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_since("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_since("1d"), Ok(Duration::from_secs(86400)));
+    }
+
+    #[test]
+    fn places_lines_before_the_first_timestamp_at_it_or_at_the_fallback() {
+        let fallback =
+            NaiveDateTime::parse_from_str("2026-10-18 12:00:00", "%Y-%m-%d %H:%M:%S").ok();
+        let mut lines = parse_lines(
+            Component::Tomcat,
+            ["Using CATALINA_BASE", "2026-10-18 09:00:00 started", "more"].into_iter(),
+        );
+        place_untimed(&mut lines, fallback);
+        let first = lines[1].time;
+        assert!(first.is_some() && first != fallback);
+        assert!(lines.iter().all(|line| line.time == first));
+
+        let mut lines = parse_lines(Component::Maven, ["[INFO] Scanning"].into_iter());
+        place_untimed(&mut lines, fallback);
+        assert_eq!(lines[0].time, fallback);
+    }
+
+    #[test]
+    fn rejects_bad_or_overflowing_since_durations() {
+        for value in ["", "10", "m", "10x", "-5m", "99999999999999999d"] {
+            assert!(parse_since(value).is_err(), "accepted '{}'", value);
+        }
+    }
+}
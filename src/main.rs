@@ -1,194 +1,205 @@
 use chrono::Local;
 use clap::App;
 use clap::Arg;
+use clap::ArgMatches;
 use colored::*;
-use serde::Deserialize;
-use serde_json::from_str;
 use std::env;
 use std::fs;
-use std::fs::remove_dir_all;
-use std::fs::File;
-use std::io;
-use std::io::{BufRead, BufReader};
-use std::path::Path;
-use std::process::{Command, Stdio};
-use std::str;
-use std::sync::Arc;
-use std::thread;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::time::{Duration, Instant};
 
-extern crate dirs;
+mod build_cache;
+mod cleanup;
+mod compose;
+mod config;
+mod debugger;
+mod deploy;
+mod docker;
+mod error;
+mod executor;
+mod health;
+mod history;
+mod logs;
+mod maven;
+mod pipeline;
+mod policy;
+mod ports;
+mod register;
+mod status;
+mod tomcat;
+mod vscode;
+mod watch;
+
+use build_cache::BuildCache;
+use cleanup::Cleanup;
+use compose::Compose;
+use config::Config;
+use deploy::LogMark;
+use docker::{Container, Network};
+use error::{Result, RunappError};
+use executor::Executor;
+use health::{Probe, Readiness};
+use logs::Component;
+use maven::{BuildSummary, Invocation, Progress};
+use pipeline::{Outcome, Pipeline, Step, Timing};
+use policy::DeletionPolicy;
+use ports::PortSpec;
+use register::Register;
+use tomcat::Tomcat;
+use watch::{Change, SourceWatcher};
 
-#[derive(Deserialize)]
-struct Register {
-    #[serde(rename = "registerName")]
-    register_name: String,
-}
-
-fn remove_if_exists(path: &str) -> io::Result<()> {
-    let path = Path::new(path);
-    if path.exists() {
-        if path.is_dir() {
-            for entry in fs::read_dir(path)? {
-                let entry = entry?;
-                if entry.path().is_dir() {
-                    remove_dir_all(entry.path())?;
-                } else if entry.path().is_file() {
-                    fs::remove_file(entry.path())?;
-                }
-            }
-            fs::remove_dir(path)?;
-        } else {
-            fs::remove_file(path)?;
-        }
-    }
-    Ok(())
-}
+extern crate dirs;
 
-fn start_services() -> io::Result<()> {
+fn start_services(ctx: &Context) -> Result<()> {
     println!("{}", "Starting authorization server...".bright_blue());
-    let auth_status = Command::new("auth-server-run").status();
-    match auth_status {
-        Ok(s) if s.success() => println!("{}", "Authorization server started.".green()),
-        Ok(_) => println!(
-            "{}",
-            "Authorization server may already be running.".yellow()
-        ),
-        Err(e) => {
-            eprintln!("{}", format!("Failed to start auth server: {}", e).red());
-            return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
+    let auth_status = ctx
+        .exec
+        .status(&mut Command::new(ctx.config.command("auth_server_run")))?;
+    if !ctx.exec.is_dry_run() {
+        if auth_status.success() {
+            println!("{}", "Authorization server started.".green());
+        } else {
+            println!(
+                "{}",
+                "Authorization server may already be running.".yellow()
+            );
         }
     }
 
-    // Give the auth server a moment to bind its port before PDP connects
-    thread::sleep(Duration::from_secs(2));
+    // PDP connects to the auth server, so it has to be up first
+    let probe = match ctx.config.get("health.auth_server_url") {
+        "" => Probe::Process(ctx.config.get("services.auth_server_process").to_string()),
+        url => Probe::Http(url.to_string()),
+    };
+    ctx.readiness.wait_ready("auth-server", &probe)?;
 
     println!("{}", "Starting PDP container...".bright_blue());
-    let pdp_status = Command::new("pdp-docker-run").status();
-    match pdp_status {
-        Ok(s) if s.success() => println!("{}", "PDP container started.".green()),
-        Ok(_) => println!("{}", "PDP container may already be running.".yellow()),
-        Err(e) => {
-            eprintln!("{}", format!("Failed to start PDP: {}", e).red());
-            return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
+    let pdp_status = ctx
+        .exec
+        .status(&mut Command::new(ctx.config.command("pdp_docker_run")))?;
+    if !ctx.exec.is_dry_run() {
+        if pdp_status.success() {
+            println!("{}", "PDP container started.".green());
+        } else {
+            println!("{}", "PDP container may already be running.".yellow());
         }
     }
 
+    match ctx.config.get("health.pdp_url") {
+        "" => {}
+        url => ctx
+            .readiness
+            .wait_ready("PDP", &Probe::Http(url.to_string()))?,
+    }
+
+    if ctx.exec.is_dry_run() {
+        return Ok(());
+    }
     println!("{}", "\nServices started:".bright_green());
-    println!("  auth-server  — running in background (logs: auth-server-logs)");
-    println!("  pdp          — running in Docker      (logs: pdp-docker-logs)");
+    println!("  auth-server  — running in background (logs: runapp logs auth-server)");
+    println!("  pdp          — running in Docker      (logs: runapp logs pdp)");
 
     Ok(())
 }
 
-fn stop_services() -> io::Result<()> {
+fn stop_services(ctx: &Context) -> Result<()> {
     println!("{}", "Stopping PDP container...".yellow());
-    let pdp_status = Command::new("pdp-docker-stop").status();
+    let pdp_status = ctx
+        .exec
+        .status(&mut Command::new(ctx.config.command("pdp_docker_stop")));
     match pdp_status {
+        Ok(_) if ctx.exec.is_dry_run() => {}
         Ok(s) if s.success() => println!("{}", "PDP container stopped.".green()),
         Ok(_) => println!("{}", "PDP container was not running.".yellow()),
         Err(e) => eprintln!("{}", format!("Failed to stop PDP: {}", e).red()),
     }
 
     println!("{}", "Stopping authorization server...".yellow());
-    let auth_status = Command::new("auth-server-stop").status();
+    let auth_status = ctx
+        .exec
+        .status(&mut Command::new(ctx.config.command("auth_server_stop")));
     match auth_status {
+        Ok(_) if ctx.exec.is_dry_run() => {}
         Ok(s) if s.success() => println!("{}", "Authorization server stopped.".green()),
         Ok(_) => println!("{}", "Authorization server was not running.".yellow()),
         Err(e) => eprintln!("{}", format!("Failed to stop auth server: {}", e).red()),
     }
 
-    println!("{}", "\nAll services stopped.".bright_green());
+    if !ctx.exec.is_dry_run() {
+        println!("{}", "\nAll services stopped.".bright_green());
+    }
 
     Ok(())
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
 
-fn clean_up(register_name: &str) -> io::Result<()> {
+fn clean_up(ctx: &Context) -> Result<()> {
+    let war_name = ctx.register.war_name();
+    let deploy_name = ctx.register.deploy_name();
+    let mysql_dir = ctx.mysql_dir()?;
+    let target_dir = ctx.config.target_dir();
+
     println!("{}", "Cleaning up and stopping services...".yellow());
-    let result = Command::new("sh")
-        .arg("-c")
-        .arg("stop_tomcat 2>/dev/null")
-        .status();
-
-    match result {
-        Ok(status) => {
-            if !status.success() {
-                println!("{}", "Tomcat not running. Continuing...".yellow());
-            }
-        }
-        Err(_) => {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Failed to execute command",
-            ));
+    match ctx.tomcat() {
+        Ok(tomcat) => tomcat.stop(&ctx.exec, ctx.config.seconds("tomcat.stop_timeout_secs"))?,
+        Err(RunappError::MissingEnv(var)) => {
+            println!(
+                "{}",
+                format!("{} is not set, skipping Tomcat.", var).yellow()
+            )
         }
+        Err(e) => return Err(e),
     }
 
-    Command::new("docker-compose")
-        .arg("down")
-        .status()
-        .map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to execute command"))?;
+    stop_compose(ctx)?;
+    remove_docker_container(ctx)?;
 
     println!("{}", "Cleaning up and stopping MySQL...".yellow());
-    if fs::metadata("mysql/data").is_ok() {
-        if fs::metadata("mysql/socket.lock").is_ok() {
-            Command::new("sh")
-                .arg("-c")
-                .arg("stop_mysql >/dev/null 2>&1")
-                .status()
-                .map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to execute command"))?;
-            let output = Command::new("pgrep")
-                .arg("mysqld")
-                .output()
-                .map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to execute command"))?;
+    if mysql_dir.join("data").exists() {
+        if mysql_dir.join("socket.lock").exists() {
+            ctx.exec.status(Command::new("sh").arg("-c").arg(format!(
+                "{} >/dev/null 2>&1",
+                ctx.config.command("stop_mysql")
+            )))?;
+            let output = ctx.exec.probe(Command::new("pgrep").arg("mysqld"))?;
             if !output.stdout.is_empty() {
-                Command::new("pkill").arg("mysqld").status().map_err(|_| {
-                    io::Error::new(io::ErrorKind::Other, "Failed to execute command")
-                })?;
+                ctx.exec.status(Command::new("pkill").arg("mysqld"))?;
             }
         }
 
         println!("{}", "\nAwaiting MySQL shutdown...\n".red());
+        ctx.readiness
+            .wait_gone("MySQL", &Probe::UnixSocket(mysql_dir.join("socket")))?;
         println!("{}", "Cleaning up files...".yellow());
-        thread::sleep(Duration::from_secs(5));
-
-        let home_dir = dirs::home_dir().expect("Home directory not found");
-        let my_cnf_path = home_dir.join(".my.cnf");
-        let mysql_path = format!("{}/mysql", std::env::var("PWD").unwrap());
-
-        remove_if_exists(my_cnf_path.to_str().unwrap())?;
-        remove_if_exists(&format!("{}/.my.cnf", mysql_path))?;
-        remove_if_exists(&format!("{}/.my.cnf", env::var("HOME").unwrap()))?;
-        remove_if_exists(&format!("target/{}.war", register_name))?;
-        remove_if_exists(&format!("target/{}", register_name))?;
-        remove_if_exists(&format!("target/war"))?;
-        remove_if_exists(&format!("target/classes"))?;
-        remove_if_exists(&format!("target/generated-sources"))?;
-        remove_if_exists(&format!("target/maven-archiver"))?;
-        remove_if_exists(&format!("target/maven-status"))?;
-        remove_if_exists(&format!(
-            "{}/webapps/{}.war",
-            env::var("CATALINA_HOME").unwrap(),
-            register_name
-        ))?;
-        remove_if_exists(&format!(
-            "{}/webapps/{}",
-            env::var("CATALINA_HOME").unwrap(),
-            register_name
-        ))?;
-        remove_if_exists(&format!("{}/bin/src", env::var("CATALINA_HOME").unwrap()))?;
-        remove_if_exists(&format!("{}/logs", env::var("CATALINA_HOME").unwrap()))?;
-        remove_if_exists(&format!(
-            "{}/compile_log.txt",
-            env::var("CATALINA_HOME").unwrap()
-        ))?;
-        remove_if_exists("jdk/*")?;
-        remove_if_exists("logs/*")?;
-        remove_if_exists("overlays/*")?;
-
-        println!("{}", "Stopped running processes".red());
+
+        let my_cnf_path = home_dir()?.join(".my.cnf");
+        let catalina_home = catalina_home()?;
+        let mut cleanup = Cleanup::new(&ctx.exec);
+        cleanup.remove(&my_cnf_path)?;
+        cleanup.remove(mysql_dir.join(".my.cnf"))?;
+        cleanup.remove(format!("{}/{}.war", target_dir, war_name))?;
+        cleanup.remove(format!("{}/{}.war.fingerprint", target_dir, war_name))?;
+        cleanup.remove(format!("{}/{}", target_dir, war_name))?;
+        cleanup.remove(format!("{}/war", target_dir))?;
+        cleanup.remove(format!("{}/classes", target_dir))?;
+        cleanup.remove(format!("{}/generated-sources", target_dir))?;
+        cleanup.remove(format!("{}/maven-archiver", target_dir))?;
+        cleanup.remove(format!("{}/maven-status", target_dir))?;
+        cleanup.remove(format!("{}/webapps/{}.war", catalina_home, deploy_name))?;
+        cleanup.remove(format!("{}/webapps/{}", catalina_home, deploy_name))?;
+        cleanup.remove(format!("{}/bin/src", catalina_home))?;
+        cleanup.remove(format!("{}/logs", catalina_home))?;
+        cleanup.remove(format!("{}/compile_log.txt", catalina_home))?;
+        cleanup.remove("jdk/*")?;
+        cleanup.remove("logs/*")?;
+        cleanup.remove("overlays/*")?;
+        cleanup.report();
+
+        if !ctx.exec.is_dry_run() {
+            println!("{}", "Stopped running processes".red());
+        }
     } else {
         println!("{}", "No local database found. Continuing...".yellow());
     }
@@ -196,89 +207,89 @@ fn clean_up(register_name: &str) -> io::Result<()> {
     Ok(())
 }
 
-fn drop_database(register_name: &str) -> io::Result<()> {
+fn drop_database(ctx: &Context) -> Result<()> {
+    let database_name = ctx.register.database_name();
+    let mysql_dir = ctx.mysql_dir()?;
+    let sql_marker = mysql_dir.join(format!("{}.sql", database_name));
+    let mut cleanup = Cleanup::new(&ctx.exec);
+
     println!("{}", "Starting to drop database...".bright_blue());
 
-    if fs::metadata(format!("mysql/{}.sql", register_name)).is_ok() {
-        println!("Dropping external database {}...", register_name);
-        Command::new("mysql_drop")
-            .status()
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to execute command"))?;
+    if sql_marker.exists() {
+        println!("Dropping external database {}...", database_name);
+        ctx.exec
+            .status(&mut Command::new(ctx.config.command("mysql_drop")))?;
 
-        std::thread::sleep(std::time::Duration::from_secs(1));
-        remove_if_exists(&format!("mysql/{}.sql", register_name))?;
+        ctx.exec.sleep(Duration::from_secs(1));
+        cleanup.remove(&sql_marker)?;
     }
 
-    let home_dir = dirs::home_dir().expect("Home directory not found");
-    let my_cnf_path = home_dir.join(".my.cnf");
-    let catalina_home = env::var("CATALINA_HOME").unwrap();
-    let mysql_path = format!("{}/mysql", std::env::var("PWD").unwrap());
+    let my_cnf_path = home_dir()?.join(".my.cnf");
+    let catalina_home = catalina_home()?;
 
-    remove_if_exists(&format!("{}/data", mysql_path))?;
-    remove_if_exists(my_cnf_path.to_str().unwrap())?;
-    remove_if_exists(&format!("{}/bin/src/*", catalina_home))?;
-    remove_if_exists(&format!("{}/logs/*", catalina_home))?;
-    remove_if_exists(&format!("{}/webapps/*", catalina_home))?;
-    remove_if_exists("logs/*")?;
+    cleanup.remove(mysql_dir.join("data"))?;
+    cleanup.remove(&my_cnf_path)?;
+    cleanup.remove(format!("{}/bin/src/*", catalina_home))?;
+    cleanup.remove(format!("{}/logs/*", catalina_home))?;
+    cleanup.remove(format!("{}/webapps/*", catalina_home))?;
+    cleanup.remove("logs/*")?;
+    cleanup.report();
 
     println!("{}", "\nDatabase dropped.".red());
 
     Ok(())
 }
 
-fn clean_local_credentials() -> std::io::Result<()> {
-    let home_dir = dirs::home_dir().expect("Home directory not found");
-    let my_cnf_path = home_dir.join(".my.cnf");
-    let mysql_my_cnf_path = Path::new("mysql/.my.cnf");
-    let mvn_compile_log_path = Path::new("tomcat/compile_log.txt");
-    let catalina_logs_path = env::var("CATALINA_HOME")
-        .map(|path| Path::new(&path).join("logs"))
-        .unwrap();
+fn clean_local_credentials(ctx: &Context) -> Result<()> {
+    let my_cnf_path = home_dir()?.join(".my.cnf");
+    let mysql_my_cnf_path = ctx.mysql_dir()?.join(".my.cnf");
+    let catalina_logs_path = Path::new(&catalina_home()?).join("logs");
 
-    println!("{}", my_cnf_path.to_str().unwrap());
+    println!("{}", my_cnf_path.display());
     println!("{}", "Cleaning up mysql credentials...".yellow());
-    remove_if_exists(my_cnf_path.to_str().unwrap())?;
-    remove_if_exists(mysql_my_cnf_path.to_str().unwrap())?;
-    remove_if_exists(mvn_compile_log_path.to_str().unwrap())?;
+    let mut cleanup = Cleanup::new(&ctx.exec);
+    cleanup.remove(&my_cnf_path)?;
+    cleanup.remove(&mysql_my_cnf_path)?;
+    cleanup.report();
     if !catalina_logs_path.exists() {
-        fs::create_dir_all(catalina_logs_path)?;
+        ctx.exec.create_dir_all(&catalina_logs_path)?;
     }
 
     Ok(())
 }
 
-fn set_mysql_envs<'a>(command: &'a mut Command, register_name: &str) -> &'a mut Command {
-    let mysql_dir = format!("{}/mysql", std::env::var("PWD").unwrap());
-    let mysql_unix_port = format!("{}/socket", &mysql_dir);
+fn mysql_envs(ctx: &Context) -> Result<Vec<(&'static str, String)>> {
+    let db_user = ctx.register.db_user();
+    let mysql_unix_port = ctx.mysql_dir()?.join("socket");
+
+    Ok(vec![
+        ("MYSQL_USER", db_user.to_string()),
+        ("MYSQL_PASSWORD", db_user.to_string()),
+        ("MYSQL_UNIX_PORT", mysql_unix_port.display().to_string()),
+        ("MYSQL_TCP_PORT", ctx.config.port("ports.mysql").to_string()),
+        ("MYSQL_DATABASE", ctx.register.database_name().to_string()),
+    ])
+}
 
-    command
-        .env("MYSQL_USER", register_name)
-        .env("MYSQL_PASSWORD", register_name)
-        .env("MYSQL_UNIX_PORT", &mysql_unix_port)
-        .env("MYSQL_DATABASE", register_name)
+fn set_mysql_envs<'a>(command: &'a mut Command, ctx: &Context) -> Result<&'a mut Command> {
+    Ok(command.envs(mysql_envs(ctx)?))
 }
 
-fn setup_local_database(register_name: &str) -> std::io::Result<()> {
+fn setup_local_database(ctx: &Context) -> Result<()> {
     println!("{}", "\nDatabase setup...".bright_blue());
     println!("{}", "Setting up mysql in env...".yellow());
 
-    let mysql_dir = format!("{}/mysql", std::env::var("PWD").unwrap());
+    let mysql_dir = ctx.mysql_dir()?;
     if fs::metadata(&mysql_dir).is_err() {
-        fs::create_dir_all(&mysql_dir)?;
+        ctx.exec.create_dir_all(&mysql_dir)?;
     }
 
-    if fs::metadata(format!("{}/data", &mysql_dir)).is_err() {
+    if fs::metadata(mysql_dir.join("data")).is_err() {
         println!("{}", "No database found. Creating...".red());
-        let mut command = Command::new("mysqlinit");
-        let status = set_mysql_envs(&mut command, register_name)
-            .status()
-            .expect("Failed to execute command");
-        if !status.success() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to initialize MySQL",
-            ));
-        }
+        let mut command = Command::new(ctx.config.command("mysqlinit"));
+        ctx.exec
+            .run(set_mysql_envs(&mut command, ctx)?)
+            .map_err(|e| e.into_database_init("Failed to initialize MySQL"))?;
     } else {
         println!(
             "{}",
@@ -287,73 +298,53 @@ fn setup_local_database(register_name: &str) -> std::io::Result<()> {
     }
 
     println!("{}", "setting up mysqlcred...".yellow());
-    let mut command = Command::new("mysqlcred");
-    let status = set_mysql_envs(&mut command, register_name)
-        .status()
-        .expect("Failed to execute command");
-    if !status.success() {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Failed to setup MySQL credentials",
-        ));
-    }
+    let mut command = Command::new(ctx.config.command("mysqlcred"));
+    ctx.exec
+        .run(set_mysql_envs(&mut command, ctx)?)
+        .map_err(|e| e.into_database_init("Failed to setup MySQL credentials"))?;
 
     Ok(())
 }
 
-fn setup_external_database(register_name: &str) -> std::io::Result<()> {
+fn setup_external_database(ctx: &Context) -> Result<()> {
+    let database_name = ctx.register.database_name();
+    let sql_marker = ctx.mysql_dir()?.join(format!("{}.sql", database_name));
+
     println!("{}", "\nsetting up mysqlcred...".yellow());
 
-    let status = Command::new("mysqlcred")
-        .status()
-        .expect("Failed to execute command");
-    if !status.success() {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Failed to setup MySQL credentials",
-        ));
-    }
+    ctx.exec
+        .run(&mut Command::new(ctx.config.command("mysqlcred")))
+        .map_err(|e| e.into_database_init("Failed to setup MySQL credentials"))?;
 
-    if fs::metadata(format!("mysql/{}.sql", register_name)).is_err() {
+    if fs::metadata(&sql_marker).is_err() {
         println!("{}", "No database found. Creating...".red());
         println!("{}", "Setting up root...".yellow());
 
-        let status = Command::new("mysqlinit_remote")
-            .status()
-            .expect("Failed to execute command");
-        if !status.success() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to create MySQL database",
-            ));
-        }
+        ctx.exec
+            .run(&mut Command::new(ctx.config.command("mysqlinit_remote")))
+            .map_err(|e| e.into_database_init("Failed to create MySQL database"))?;
 
-        println!("Creating database {}...", register_name);
-        fs::File::create(format!("mysql/{}.sql", register_name))?;
+        println!("Creating database {}...", database_name);
+        ctx.exec.create_file(&sql_marker)?;
     } else {
         println!("{}", "Local database already setup. Continuing...".yellow());
 
-        let status = Command::new("mysql_infile")
-            .status()
-            .expect("Failed to execute command");
-        if !status.success() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to load local MySQL file",
-            ));
-        }
+        ctx.exec
+            .run(&mut Command::new(ctx.config.command("mysql_infile")))
+            .map_err(|e| e.into_database_init("Failed to load local MySQL file"))?;
     }
 
     Ok(())
 }
 
-fn start_database(register_name: &str) -> std::io::Result<()> {
-    let mysql_dir = format!("{}/mysql", std::env::var("PWD").unwrap());
-    let mysql_unix_port = format!("{}/socket", &mysql_dir);
-    let socket_lock_exists = fs::metadata("mysql/socket.lock").is_ok();
-    let mysql_running = !Command::new("pgrep")
-        .arg("mysqld")
-        .output()?
+fn start_database(ctx: &Context) -> Result<()> {
+    let mysql_dir = ctx.mysql_dir()?;
+    let mysql_unix_port = mysql_dir.join("socket");
+    let mysql_probe = Probe::UnixSocket(mysql_unix_port.clone());
+    let socket_lock_exists = fs::metadata(mysql_dir.join("socket.lock")).is_ok();
+    let mysql_running = !ctx
+        .exec
+        .probe(Command::new("pgrep").arg("mysqld"))?
         .stdout
         .is_empty();
 
@@ -363,11 +354,8 @@ fn start_database(register_name: &str) -> std::io::Result<()> {
                 "{}",
                 "Starting MySQL as no socket.lock file and MySQL is not running...".bright_blue()
             );
-            let mut command = Command::new("start_mysql");
-            set_mysql_envs(&mut command, register_name)
-                .status()
-                .expect("Failed to execute command");
-            thread::sleep(Duration::from_secs(3));
+            let mut command = Command::new(ctx.config.command("start_mysql"));
+            ctx.exec.status(set_mysql_envs(&mut command, ctx)?)?;
         }
         (true, true) => {
             println!(
@@ -381,124 +369,298 @@ fn start_database(register_name: &str) -> std::io::Result<()> {
                 "MySQL is running, but no socket.lock file found. Killing MySQL and restarting..."
                     .red()
             );
-            Command::new("pkill")
-                .arg("mysqld")
-                .status()
-                .expect("Failed to execute command");
-            thread::sleep(Duration::from_secs(3));
-            let mut command = Command::new("start_mysql");
-            set_mysql_envs(&mut command, register_name)
-                .status()
-                .expect("Failed to execute command");
-            thread::sleep(Duration::from_secs(3));
+            ctx.exec.status(Command::new("pkill").arg("mysqld"))?;
+            ctx.readiness.wait_gone("MySQL", &mysql_probe)?;
+            let mut command = Command::new(ctx.config.command("start_mysql"));
+            ctx.exec.status(set_mysql_envs(&mut command, ctx)?)?;
         }
         _ => {}
     }
 
+    ctx.readiness.wait_ready("MySQL", &mysql_probe)?;
+
     println!(
         "{}",
         "Setting load local inline files permissions...".yellow()
     );
-    thread::sleep(Duration::from_secs(3));
-    let mut command = Command::new("mysql_infile");
-    command
-        .env("MYSQL_UNIX_PORT", &mysql_unix_port)
-        .status()
-        .expect("Failed to execute command");
-    thread::sleep(Duration::from_secs(3));
+    let mut command = Command::new(ctx.config.command("mysql_infile"));
+    ctx.exec
+        .status(command.env("MYSQL_UNIX_PORT", &mysql_unix_port))?;
 
     Ok(())
 }
 
-fn compile_maven() -> Result<(), String> {
-    let target_exists = fs::metadata("target").is_ok();
+fn compile_maven(ctx: &Context) -> Result<Outcome> {
+    let target_dir = ctx.config.target_dir();
+    let compile_log = ctx.config.compile_log();
+    let maven = Invocation::new(&ctx.config, &ctx.maven_args);
+    let cache = BuildCache::new(target_dir, ctx.register.war_name());
+    let fingerprint =
+        build_cache::fingerprint(&env::current_dir()?, &maven.fingerprint_settings())?;
 
-    if target_exists {
+    if ctx.rebuild {
+        println!(
+            "{}",
+            "Rebuild requested, ignoring the build cache.".yellow()
+        );
+    } else if cache.is_fresh(&fingerprint) {
+        println!(
+            "{}",
+            format!(
+                "{}/{}.war is up to date, skipping the Maven build.",
+                target_dir,
+                ctx.register.war_name()
+            )
+            .green()
+        );
+        return Ok(Outcome::Skipped);
+    }
+
+    if fs::metadata(target_dir).is_ok() {
         println!("{}", "Target directory found. Cleaning up...".yellow());
-        fs::remove_dir_all("target").map_err(|e| e.to_string())?;
+        ctx.exec.remove(Path::new(target_dir))?;
     } else {
         println!("{}", "No target directory found...".red());
     }
 
-    let mvn_command = if target_exists { "package" } else { "install" };
-
-    let file = File::create("tomcat/compile_log.txt").map_err(|e| e.to_string())?;
-
-    let status = Command::new("mvn")
-        .args(&["clean", mvn_command, "-DskipTests"])
-        .stdout(Stdio::from(file))
-        .status()
-        .map_err(|e| e.to_string())?;
+    println!("{}", format!("Running {}...", maven).yellow());
+    let mut command = maven.command();
+    let progress = Progress::start(ctx.verbose);
+    let status = ctx
+        .exec
+        .stream_logged(&mut command, Path::new(compile_log), |line| {
+            progress.line(line)
+        });
+    let status = match status {
+        Ok(status) => status,
+        Err(e) => {
+            progress.finish(false);
+            return Err(e);
+        }
+    };
+    progress.finish(status.success());
 
     if !status.success() {
-        let file = File::open("tomcat/compile_log.txt").map_err(|e| e.to_string())?;
-        let reader = BufReader::new(file);
-        let lines: Vec<String> = reader
-            .lines()
-            .collect::<Result<_, _>>()
-            .map_err(|e| e.to_string())?;
-        let last_50_lines = lines
-            .iter()
-            .rev()
-            .take(50)
-            .map(AsRef::as_ref)
-            .collect::<Vec<_>>();
-        return Err(format!(
-            "Maven compile failed. Last 50 lines of compile log:\n{}",
-            last_50_lines.join("\n")
-        ));
+        let log = fs::read(compile_log)?;
+        let summary = BuildSummary::parse(&String::from_utf8_lossy(&log), &env::current_dir()?);
+        // With --json the summary is printed once the pipeline has finished
+        if !ctx.json {
+            summary.print(compile_log);
+        }
+        return Err(RunappError::MavenBuild(format!(
+            "{} (full log in {})",
+            summary.headline(),
+            compile_log
+        )));
     }
 
-    Ok(())
+    cache.store(&ctx.exec, &fingerprint)?;
+    Ok(Outcome::Ok)
 }
 
-fn check_port_8080() {
-    let output = Command::new("sh")
-        .arg("-c")
-        .arg("lsof -i :8080 | grep LISTEN")
-        .output()
-        .expect("Failed to execute command");
-
-    if !output.stdout.is_empty() {
-        println!("{}", "Port 8080 is in use by the following process:".red());
-        println!("{}", String::from_utf8_lossy(&output.stdout));
-        panic!("Cannot start Tomcat because port 8080 is in use.");
+/// With `--json`, prints the summary of a failed Maven build as JSON after
+/// the pipeline, so the output of steps running alongside the build does not
+/// end up in the middle of it.
+fn print_build_failure_json(ctx: &Context, e: &RunappError) -> Result<()> {
+    let maven_failed = match e {
+        RunappError::StepFailed { source, .. } => matches!(**source, RunappError::MavenBuild(_)),
+        e => matches!(e, RunappError::MavenBuild(_)),
+    };
+    if !ctx.json || !maven_failed {
+        return Ok(());
     }
+    let log = fs::read(ctx.config.compile_log())?;
+    let summary = BuildSummary::parse(&String::from_utf8_lossy(&log), &env::current_dir()?);
+    let out = serde_json::to_string_pretty(&summary).map_err(std::io::Error::other)?;
+    println!("{}", out);
+    Ok(())
 }
 
-fn start_tomcat(register_name: &str) -> std::io::Result<()> {
+fn start_tomcat(ctx: &Context) -> Result<()> {
+    let deploy_name = ctx.register.deploy_name();
+
     println!("{}", "Local environment detected...".bright_blue());
     println!("{}", "Setting up Tomcat...".yellow());
 
-    let catalina_home = env::var("CATALINA_HOME").unwrap();
-    let war_file_path = format!("{}/webapps/{}.war", catalina_home, register_name);
-    let webapp_folder_path = format!("{}/webapps/{}", catalina_home, register_name);
+    let catalina_home = catalina_home()?;
+    let war_file_path = format!("{}/webapps/{}.war", catalina_home, deploy_name);
+    let webapp_folder_path = format!("{}/webapps/{}", catalina_home, deploy_name);
 
     if fs::metadata(&war_file_path).is_ok() {
         println!("{}", "delete old war file...".red());
-        fs::remove_file(&war_file_path)?;
+        ctx.exec.remove(Path::new(&war_file_path))?;
     }
     if fs::metadata(&webapp_folder_path).is_ok() {
         println!("{}", "delete webapps folder...".red());
-        fs::remove_dir_all(&webapp_folder_path)?;
+        ctx.exec.remove(Path::new(&webapp_folder_path))?;
     }
 
     println!("{}", "Deploying new WAR...".yellow());
-    fs::copy(format!("target/{}.war", register_name), &war_file_path)?;
+    ctx.exec.copy(
+        Path::new(&format!(
+            "{}/{}.war",
+            ctx.config.target_dir(),
+            ctx.register.war_name()
+        )),
+        Path::new(&war_file_path),
+    )?;
+
+    let debug = ctx.config.flag("debug.enabled");
+    let suspend = debug && ctx.config.flag("debug.suspend");
+    let mut timeout = ctx.config.seconds("tomcat.start_timeout_secs");
+    if suspend {
+        // Tomcat only gets going once someone attaches, however long that takes
+        timeout = Duration::MAX;
+        println!(
+            "{}",
+            format!(
+                "Tomcat will wait for a debugger to attach on port {}.",
+                ctx.config.debug_port()
+            )
+            .bright_blue()
+        );
+        debugger::print_attach_configs(ctx.register.name(), ctx.config.debug_port());
+    }
 
     println!("Starting Tomcat...");
-    Command::new("sh")
-        .arg("-c")
-        .arg(format!("{}/bin/catalina.sh jpda start", catalina_home))
-        .status()
-        .expect("Failed to execute command");
+    let tomcat = ctx.tomcat()?;
+    let mark = LogMark::new(&tomcat.logs_dir());
+    let envs = if debug {
+        vec![
+            (
+                "JPDA_ADDRESS",
+                format!("localhost:{}", ctx.config.debug_port()),
+            ),
+            ("JPDA_SUSPEND", if suspend { "y" } else { "n" }.to_string()),
+        ]
+    } else {
+        Vec::new()
+    };
+    let started = tomcat.start(&ctx.exec, debug, &envs, timeout)?;
+    if !ctx.exec.is_dry_run() {
+        println!("{}", format!("Tomcat is up: {}", started).green());
+    }
+    if debug && !suspend {
+        debugger::print_attach_configs(ctx.register.name(), ctx.config.debug_port());
+    }
 
+    if ctx.exec.is_dry_run() {
+        return Ok(());
+    }
+    deploy::verify(
+        &mark,
+        &format!("{}.war", deploy_name),
+        &ctx.app_url(),
+        &ctx.readiness,
+    )
+}
+
+fn ensure_tomcat_running(ctx: &Context) -> Result<()> {
+    if ctx.tomcat()?.pid().is_none() && !ctx.exec.is_dry_run() {
+        return Err(RunappError::NotRunning("Tomcat"));
+    }
+    Ok(())
+}
+
+/// Swaps the freshly built WAR into the running Tomcat and lets autodeploy
+/// reload it. The WAR is copied next to the old one first and then renamed
+/// over it, so Tomcat never sees a half-written archive.
+fn redeploy_war(ctx: &Context) -> Result<()> {
+    let tomcat = ctx.tomcat()?;
+    let deploy_name = ctx.register.deploy_name();
+    let webapps = Path::new(&catalina_home()?).join("webapps");
+    let war_file = format!("{}.war", deploy_name);
+    let staged = webapps.join(format!("{}.new", war_file));
+
+    let mark = LogMark::new(&tomcat.logs_dir());
+    let start = Instant::now();
+    println!("{}", "Swapping in the new WAR...".yellow());
+    ctx.exec.copy(
+        Path::new(&format!(
+            "{}/{}.war",
+            ctx.config.target_dir(),
+            ctx.register.war_name()
+        )),
+        &staged,
+    )?;
+    ctx.exec.rename(&staged, &webapps.join(&war_file))?;
+    if ctx.exec.is_dry_run() {
+        return Ok(());
+    }
+
+    deploy::wait_deployed(
+        &mark,
+        &war_file,
+        ctx.config.seconds("tomcat.start_timeout_secs"),
+    )?;
+    deploy::verify(&mark, &war_file, &ctx.app_url(), &ctx.readiness)?;
+    println!(
+        "{}",
+        format!(
+            "Reloaded {} in {:.1}s",
+            ctx.register.context_path(),
+            start.elapsed().as_secs_f64()
+        )
+        .green()
+    );
     Ok(())
 }
 
-fn copy_dir_to(src_dir: &Path, dst_dir: &Path) -> std::io::Result<()> {
+/// Rebuilds and redeploys whenever the sources change, until interrupted. A
+/// failed build is reported and the watcher carries on.
+fn watch_sources(ctx: &Context) -> Result<()> {
+    ensure_tomcat_running(ctx)?;
+    let root = env::current_dir()?;
+    let watcher = SourceWatcher::new(&root, ctx.config.db_source_dir())?;
+    let rebuild = Pipeline::new("rebuild")
+        .step(compile_step())
+        .step(Step::new("redeploy", redeploy_war).after(&["compile"]));
+    let db_files = Pipeline::new("db-files").step(Step::new("copy-db-files", copy_db_files));
+
+    loop {
+        println!(
+            "{}",
+            "\nWatching src/main and pom.xml for changes (Ctrl+C to stop)...".bright_blue()
+        );
+        let (pipeline, changed) = match watcher.next()? {
+            Change::Build(changed) => (&rebuild, changed),
+            Change::DbFiles(changed) => (&db_files, changed),
+        };
+        // Editors' temporary files are gone by now; name a file that is not
+        let first = changed
+            .iter()
+            .find(|path| path.exists())
+            .unwrap_or(&changed[0]);
+        let first = first.strip_prefix(&root).unwrap_or(first);
+        match changed.len() {
+            1 => println!("{}", format!("Changed: {}", first.display()).yellow()),
+            n => println!(
+                "{}",
+                format!("Changed: {} and {} more", first.display(), n - 1).yellow()
+            ),
+        }
+        if let Err(e) = pipeline.run(ctx) {
+            if let Err(json_error) = print_build_failure_json(ctx, &e) {
+                report_error(&json_error);
+            }
+            report_error(&ctx.attribute_program(e));
+        }
+    }
+}
+
+fn write_vscode_config(ctx: &Context) -> Result<()> {
+    println!("{}", "Updating the VS Code configuration...".yellow());
+    vscode::update(
+        &ctx.exec,
+        &env::current_dir()?,
+        ctx.register.name(),
+        ctx.config.debug_port(),
+    )
+}
+
+fn copy_dir_to(exec: &Executor, src_dir: &Path, dst_dir: &Path) -> Result<()> {
     if !dst_dir.is_dir() {
-        fs::create_dir_all(dst_dir)?;
+        exec.create_dir_all(dst_dir)?;
     }
 
     for entry_result in src_dir.read_dir()? {
@@ -508,32 +670,44 @@ fn copy_dir_to(src_dir: &Path, dst_dir: &Path) -> std::io::Result<()> {
         let dst_path = dst_dir.join(entry.file_name());
 
         if file_type.is_file() {
-            fs::copy(src_path, dst_path)?;
+            exec.copy(&src_path, &dst_path)?;
         } else if file_type.is_dir() {
-            copy_dir_to(&src_path, &dst_path)?;
+            copy_dir_to(exec, &src_path, &dst_path)?;
         }
     }
 
     Ok(())
 }
 
-fn copy_db_files() -> std::io::Result<()> {
-    let catalina_home = env::var("CATALINA_HOME").unwrap();
-    let db_path = format!("{}/bin/src/main/resources/db/application", catalina_home);
-    let db_path = Path::new(&db_path);
+fn copy_db_files(ctx: &Context) -> Result<()> {
+    let catalina_home = catalina_home()?;
+    let db_source_dir = ctx.config.db_source_dir();
+    let db_path = Path::new(&catalina_home).join("bin").join(db_source_dir);
+    let db_path = db_path.as_path();
 
     if !db_path.exists() {
         println!("{}", "DB path does not exist. Creating...".yellow());
-        fs::create_dir_all(&db_path)?;
+        ctx.exec.create_dir_all(db_path)?;
     }
 
     println!("{}", "Copying db files...".yellow());
-    let src_path = Path::new("./src/main/resources/db/application/");
-    copy_dir_to(&src_path, &db_path)?;
+    let src_path = Path::new(db_source_dir);
+    copy_dir_to(&ctx.exec, src_path, db_path)?;
 
     Ok(())
 }
 
+fn catalina_home() -> Result<String> {
+    env::var("CATALINA_HOME")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .ok_or(RunappError::MissingEnv("CATALINA_HOME"))
+}
+
+fn home_dir() -> Result<PathBuf> {
+    dirs::home_dir().ok_or(RunappError::MissingEnv("HOME"))
+}
+
 fn format_duration(duration: Duration) -> String {
     let total_secs = duration.as_secs();
     let minutes = total_secs / 60;
@@ -547,7 +721,7 @@ fn format_duration(duration: Duration) -> String {
     }
 }
 
-fn exit_timestamp(start_time: Instant) {
+fn exit_timestamp(start_time: Instant, timings: &[Timing]) {
     let end_timestamp = Local::now().format("%d-%m-%Y %H:%M:%S").to_string();
     let duration = Instant::now().duration_since(start_time);
 
@@ -563,167 +737,1318 @@ fn exit_timestamp(start_time: Instant) {
         "{}",
         format!("   Total execution time: {}", format_duration(duration)).bright_green()
     );
+    if !timings.is_empty() {
+        println!();
+        print_timings(timings, duration);
+    }
     println!(
         "{}",
         "--------------------------------------------------".bright_green()
     );
 }
 
-fn main() -> std::io::Result<()> {
+/// Prints how long each step took and its share of `total`. The Maven build
+/// runs alongside the database setup, so when it overlapped other steps it is
+/// listed on its own line below them.
+fn print_timings(timings: &[Timing], total: Duration) {
+    let parallel = |timing: &Timing| {
+        timing.name == "compile"
+            && timings
+                .iter()
+                .any(|other| other.name != timing.name && timing.overlaps(other))
+    };
+    println!(
+        "   {:<24} {:>12}  {:<7}  {:>6}",
+        "Step", "Time", "Status", "Share"
+    );
+    for timing in timings.iter().filter(|timing| !parallel(timing)) {
+        print_timing(timing.name, timing, total);
+    }
+    for timing in timings.iter().filter(|timing| parallel(timing)) {
+        print_timing(&format!("{} (in parallel)", timing.name), timing, total);
+    }
+}
+
+fn print_timing(label: &str, timing: &Timing, total: Duration) {
+    let (time, status, share) = match timing.outcome {
+        Outcome::Skipped => ("-".to_string(), "skipped".dimmed(), "-".to_string()),
+        outcome => (
+            format_duration(timing.duration),
+            if outcome == Outcome::Ok {
+                "ok".green()
+            } else {
+                "failed".red()
+            },
+            format!(
+                "{:.1}%",
+                timing.duration.as_secs_f64() * 100.0 / total.as_secs_f64().max(f64::EPSILON)
+            ),
+        ),
+    };
+    println!("   {:<24} {:>12}  {:<7}  {:>6}", label, time, status, share);
+}
+
+fn stop_tomcat(ctx: &Context) -> Result<()> {
+    println!("{}", "Stopping running services...".red());
+    ctx.tomcat()?
+        .stop(&ctx.exec, ctx.config.seconds("tomcat.stop_timeout_secs"))
+}
+
+/// Takes down the compose project, leaving other projects alone.
+fn stop_compose(ctx: &Context) -> Result<()> {
+    let compose = match ctx.compose() {
+        Ok(compose) => compose,
+        Err(RunappError::CommandNotFound { .. }) => {
+            println!(
+                "{}",
+                "Docker Compose is not installed, skipping it.".yellow()
+            );
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    if !compose.can_down() {
+        println!(
+            "{}",
+            "No compose file found, skipping docker-compose.".yellow()
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        format!("Stopping compose project {}...", compose.project(&ctx.exec)).red()
+    );
+    compose.down(&ctx.exec)
+}
+
+fn compose_command(ctx: &Context, matches: &ArgMatches) -> Result<()> {
+    let compose = ctx.compose()?;
+    match matches.subcommand() {
+        Some(("up", sub_matches)) => {
+            let services: Vec<String> = sub_matches
+                .values_of("services")
+                .map(|values| values.map(str::to_string).collect())
+                .unwrap_or_default();
+            compose.up(&ctx.exec, &services)
+        }
+        Some(("logs", sub_matches)) => compose.logs(
+            &ctx.exec,
+            sub_matches.value_of("service").expect("required by clap"),
+            sub_matches.is_present("follow"),
+        ),
+        _ => {
+            let services = compose.services(&ctx.exec)?;
+            println!(
+                "{}",
+                format!(
+                    "Compose project {} ({}, {})",
+                    compose.project(&ctx.exec),
+                    compose
+                        .file()
+                        .map(Path::display)
+                        .expect("checked by services"),
+                    compose.cli()
+                )
+                .bright_blue()
+            );
+            for service in services {
+                let state = if service.running {
+                    "running".green()
+                } else {
+                    "stopped".red()
+                };
+                println!("  {:<24} {}", service.name, state);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn logs_command(ctx: &Context, matches: &ArgMatches) -> Result<()> {
+    let options = logs::Options {
+        components: matches
+            .values_of("components")
+            .map(|values| values.filter_map(Component::parse).collect())
+            .unwrap_or_default(),
+        follow: matches.is_present("follow"),
+        since: matches
+            .value_of("since")
+            .map(|since| logs::parse_since(since).expect("validated by clap")),
+        grep: matches.value_of("grep").map(str::to_string),
+    };
+    logs::show(ctx, &options)
+}
+
+fn build_docker_image(ctx: &Context) -> Result<()> {
+    let image = ctx.image();
+    println!("{}", format!("Building image {}...", image).yellow());
+    let start = Instant::now();
+    ctx.exec
+        .run(Command::new("docker").args(["build", "-t", &image, "."]))?;
+    if ctx.exec.is_dry_run() {
+        return Ok(());
+    }
+
+    let built = format_duration(start.elapsed());
+    match docker::image_id(&ctx.exec, &image)? {
+        Some(id) => println!(
+            "{}",
+            format!(
+                "Built image {} ({}) in {}",
+                image,
+                docker::short_id(&id),
+                built
+            )
+            .green()
+        ),
+        None => println!("{}", format!("Built image {} in {}", image, built).green()),
+    }
+    Ok(())
+}
+
+fn run_docker_container(ctx: &Context) -> Result<()> {
+    let name = ctx.container_name();
+    let image = ctx.image();
+    let http_port = ctx.config.http_port();
+    let network = match ctx.config.get("docker.network") {
+        "host" => Network::Host,
+        _ => Network::Bridge {
+            host_port: http_port,
+            container_port: ctx.config.port("ports.container_http"),
+            mysql_dir: ctx.mysql_dir()?,
+        },
+    };
+
+    println!(
+        "{}",
+        format!("Starting container {} from {}...", name, image).yellow()
+    );
+    Container {
+        name: &name,
+        image: &image,
+        network,
+        envs: mysql_envs(ctx)?,
+    }
+    .run(&ctx.exec)?;
+
+    // docker-proxy accepts connections on the published port before the app
+    // in the container listens, so only an HTTP answer means it is up
+    ctx.readiness
+        .wait_ready(&format!("container {}", name), &Probe::Http(ctx.app_url()))?;
+    println!(
+        "{}",
+        format!(
+            "Container {} is running: http://localhost:{}{}/",
+            name,
+            http_port,
+            ctx.register.context_path().trim_end_matches('/')
+        )
+        .green()
+    );
+    Ok(())
+}
+
+fn remove_docker_container(ctx: &Context) -> Result<()> {
+    let name = ctx.container_name();
+    println!("{}", format!("Removing container {}...", name).yellow());
+    let removed = match docker::remove(&ctx.exec, &name) {
+        Ok(removed) => removed,
+        Err(RunappError::CommandNotFound { .. }) => {
+            println!(
+                "{}",
+                "Docker is not installed, so there is no container to remove.".yellow()
+            );
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    if ctx.exec.is_dry_run() {
+        return Ok(());
+    }
+    if removed {
+        println!("{}", format!("Container {} removed.", name).green());
+    } else {
+        println!(
+            "{}",
+            format!("No container {}. Continuing...", name).yellow()
+        );
+    }
+    Ok(())
+}
+
+fn print_dry_run(exec: &Executor) {
+    let actions = exec.actions();
+    println!(
+        "{}",
+        format!("\nDry run: {} actions would be performed:", actions.len()).bright_blue()
+    );
+    for (i, action) in actions.iter().enumerate() {
+        println!("{:>3}. {}", i + 1, action);
+    }
+}
+
+fn show_config(config: &Config) {
+    println!(
+        "{}",
+        format!(
+            "Project file: {}{}",
+            config::PROJECT_FILE,
+            if Path::new(config::PROJECT_FILE).exists() {
+                ""
+            } else {
+                " (not found)"
+            }
+        )
+        .bright_blue()
+    );
+    if let Some(user_file) = config::user_file() {
+        println!(
+            "{}",
+            format!(
+                "User file:    {}{}",
+                user_file.display(),
+                if user_file.exists() {
+                    ""
+                } else {
+                    " (not found)"
+                }
+            )
+            .bright_blue()
+        );
+    }
+    println!();
+
+    let width = config
+        .settings()
+        .map(|(key, _)| key.len())
+        .max()
+        .unwrap_or(0);
+    for (key, setting) in config.settings() {
+        let source = format!("({})", setting.source);
+        let source = match setting.source {
+            config::Source::Default => source.normal(),
+            _ => source.yellow(),
+        };
+        println!(
+            "{:width$} = {:30} {}",
+            key,
+            setting.value,
+            source,
+            width = width
+        );
+    }
+}
+
+struct Context {
+    register: Register,
+    config: Config,
+    exec: Executor,
+    readiness: Readiness,
+    /// Print reports as JSON instead of text.
+    json: bool,
+    /// Stream raw Maven output instead of the progress line.
+    verbose: bool,
+    /// Build even when the build cache says the WAR is up to date.
+    rebuild: bool,
+    /// Extra arguments for Maven, given after `--`.
+    maven_args: Vec<String>,
+}
+
+impl Context {
+    /// Points a "command not found" error at the setting that named the
+    /// program, if one did.
+    fn attribute_program(&self, e: RunappError) -> RunappError {
+        e.with_program_setting(|program| self.config.program_setting(program))
+    }
+
+    fn mysql_dir(&self) -> Result<PathBuf> {
+        Ok(env::current_dir()?.join(self.config.mysql_dir()))
+    }
+
+    fn tomcat(&self) -> Result<Tomcat> {
+        Ok(Tomcat::new(
+            Path::new(&catalina_home()?),
+            env::current_dir()?.join(self.config.catalina_pid()),
+        ))
+    }
+
+    /// The image `runapp docker` builds.
+    fn image(&self) -> String {
+        format!("{}:latest", self.register.name())
+    }
+
+    /// The compose project: `docker.compose_project`, or the one Compose
+    /// picks for the compose file.
+    fn compose(&self) -> Result<Compose> {
+        Compose::detect(
+            &self.exec,
+            &env::current_dir()?,
+            self.config.get("docker.compose_file"),
+            self.config.get("docker.compose_project"),
+        )
+    }
+
+    fn container_name(&self) -> String {
+        container_name(&self.config, &self.register)
+    }
+
+    /// The URL that answers once the webapp is deployed: its context root,
+    /// or `health.app_path` below it.
+    fn app_url(&self) -> String {
+        let context = self.register.context_path();
+        let path = match self.config.get("health.app_path").trim() {
+            "" => "/".to_string(),
+            path => format!("/{}", path.trim_start_matches('/')),
+        };
+        format!(
+            "http://localhost:{}{}{}",
+            self.config.http_port(),
+            context.trim_end_matches('/'),
+            path
+        )
+    }
+}
+
+fn compile_step() -> Step<Context> {
+    Step::with_outcome("compile", compile_maven)
+}
+
+fn services_step(
+    pipeline: Pipeline<Context>,
+    enabled: bool,
+    after: &[&'static str],
+) -> Pipeline<Context> {
+    if enabled {
+        pipeline.step(Step::new("services", start_services).after(after))
+    } else {
+        pipeline
+    }
+}
+
+/// The container `runapp docker` runs: `docker.container`, or `runapp-<register>`.
+fn container_name(config: &Config, register: &Register) -> String {
+    match config.get("docker.container") {
+        "" => format!("runapp-{}", register.name()),
+        name => name.to_string(),
+    }
+}
+
+/// Checks that the ports `mode` uses are free, adding the check to `timings`
+/// unless the mode uses none.
+fn check_ports(
+    mode: &str,
+    config: &mut Config,
+    register: &Register,
+    exec: &Executor,
+    readiness: &Readiness,
+    timings: &mut Vec<Timing>,
+) -> Result<()> {
+    let started = Instant::now();
+    let result = match mode_ports(mode, config, register, exec) {
+        Ok(ports) if ports.is_empty() => return Ok(()),
+        Ok(ports) => ports::ensure_free(config, &ports, exec, readiness),
+        Err(e) => Err(e),
+    };
+    timings.push(Timing {
+        name: "port-check",
+        started,
+        duration: started.elapsed(),
+        outcome: if result.is_ok() {
+            Outcome::Ok
+        } else {
+            Outcome::Failed
+        },
+    });
+    result
+}
+
+/// Ports that have to be free before a mode starts anything. MySQL's port is
+/// only checked when our own MySQL is not already running, and the HTTP port
+/// in docker mode only when our container is not (it is replaced anyway).
+fn mode_ports(
+    mode: &str,
+    config: &Config,
+    register: &Register,
+    exec: &Executor,
+) -> Result<Vec<PortSpec>> {
+    let http = PortSpec {
+        key: "ports.http",
+        label: "Tomcat HTTP",
+        movable: false,
+    };
+    let debug = PortSpec {
+        key: "ports.debug",
+        label: "JPDA debug",
+        movable: true,
+    };
+    let mysql = PortSpec {
+        key: "ports.mysql",
+        label: "MySQL",
+        movable: true,
+    };
+
+    if mode == "docker" {
+        let socket_lock = env::current_dir()?
+            .join(config.mysql_dir())
+            .join("socket.lock");
+        let mut specs = Vec::new();
+        if docker::is_running(exec, &container_name(config, register)) != Some(true) {
+            specs.push(PortSpec {
+                movable: config.get("docker.network") != "host",
+                ..http
+            });
+        }
+        if !socket_lock.exists() {
+            specs.push(mysql);
+        }
+        return Ok(specs);
+    }
+
+    let mut specs = match mode {
+        "local" | "code" | "test" => vec![http],
+        _ => return Ok(Vec::new()),
+    };
+    if config.flag("debug.enabled") {
+        specs.push(debug);
+    }
+    if mode == "local" {
+        let socket_lock = env::current_dir()?
+            .join(config.mysql_dir())
+            .join("socket.lock");
+        if !socket_lock.exists() {
+            specs.push(mysql);
+        }
+    }
+    Ok(specs)
+}
+
+fn mode_pipeline(mode: &str, services: bool) -> Pipeline<Context> {
+    match mode {
+        "local" => {
+            let pipeline = Pipeline::new("local")
+                .step(Step::new("stop-tomcat", stop_tomcat))
+                .step(compile_step().after(&["stop-tomcat"]))
+                .step(
+                    Step::new("clean-credentials", clean_local_credentials).after(&["stop-tomcat"]),
+                )
+                .step(
+                    Step::new("setup-database", setup_local_database).after(&["clean-credentials"]),
+                )
+                .step(Step::new("start-database", start_database).after(&["setup-database"]))
+                .step(Step::new("deploy", start_tomcat).after(&["compile", "start-database"]));
+            services_step(pipeline, services, &["deploy"])
+        }
+        "code" => {
+            let pipeline = Pipeline::new("code")
+                .step(Step::new("vscode-config", write_vscode_config))
+                .step(Step::new("stop-tomcat", stop_tomcat))
+                .step(compile_step().after(&["stop-tomcat"]))
+                .step(
+                    Step::new("clean-credentials", clean_local_credentials).after(&["stop-tomcat"]),
+                )
+                .step(
+                    Step::new("setup-database", setup_external_database)
+                        .after(&["clean-credentials"]),
+                )
+                .step(Step::new("deploy", start_tomcat).after(&["compile", "setup-database"]));
+            services_step(pipeline, services, &["deploy"])
+        }
+        "docker" => {
+            let pipeline = Pipeline::new("docker")
+                .step(Step::new("stop-compose", stop_compose))
+                .step(compile_step().after(&["stop-compose"]))
+                .step(
+                    Step::new("clean-credentials", clean_local_credentials)
+                        .after(&["stop-compose"]),
+                )
+                .step(
+                    Step::new("setup-database", setup_local_database).after(&["clean-credentials"]),
+                )
+                .step(Step::new("start-database", start_database).after(&["setup-database"]))
+                .step(
+                    Step::new("docker-build", build_docker_image)
+                        .after(&["compile", "start-database"]),
+                )
+                .step(Step::new("docker-run", run_docker_container).after(&["docker-build"]));
+            services_step(pipeline, services, &["docker-run"])
+        }
+        "test" => {
+            let pipeline = Pipeline::new("test")
+                .step(compile_step())
+                .step(Step::new("copy-db-files", copy_db_files).after(&["compile"]))
+                .step(Step::new("deploy", start_tomcat).after(&["copy-db-files"]));
+            services_step(pipeline, services, &["deploy"])
+        }
+        "redeploy" => Pipeline::new("redeploy")
+            .step(Step::new("check-tomcat", ensure_tomcat_running))
+            .step(compile_step().after(&["check-tomcat"]))
+            .step(Step::new("redeploy", redeploy_war).after(&["compile"])),
+        "clean" => Pipeline::new("clean")
+            .step(Step::new("stop-services", stop_services))
+            .step(Step::new("clean-up", clean_up).after(&["stop-services"])),
+        "drop" => Pipeline::new("drop")
+            .step(Step::new("stop-services", stop_services))
+            .step(Step::new("clean-up", clean_up).after(&["stop-services"]))
+            .step(Step::new("drop-database", drop_database).after(&["clean-up"])),
+        "services-start" => {
+            Pipeline::new("services-start").step(Step::new("services", start_services))
+        }
+        "services-stop" => {
+            Pipeline::new("services-stop").step(Step::new("stop-services", stop_services))
+        }
+        _ => {
+            let pipeline = Pipeline::new("default")
+                .step(compile_step())
+                .step(Step::new("clean-credentials", clean_local_credentials))
+                .step(
+                    Step::new("setup-database", setup_external_database)
+                        .after(&["clean-credentials"]),
+                )
+                .step(
+                    Step::new("copy-db-files", copy_db_files).after(&["compile", "setup-database"]),
+                );
+            services_step(pipeline, services, &["copy-db-files"])
+        }
+    }
+}
+
+/// The arguments after `--`, on whichever (sub)command was used.
+fn passthrough_args(matches: &ArgMatches) -> Vec<String> {
+    let matches = match matches.subcommand() {
+        Some((_, sub_matches)) => sub_matches,
+        None => matches,
+    };
+    if !matches.try_contains_id("maven-args").unwrap_or(false) {
+        return Vec::new();
+    }
+    matches
+        .values_of("maven-args")
+        .map(|values| values.map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+fn main() {
+    if let Err(e) = run() {
+        report_error(&e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn report_error(e: &RunappError) {
+    eprintln!("{}", format!("Error: {}", e).red());
+    if let Some(hint) = e.hint() {
+        eprintln!("{}", format!("Hint: {}", hint).yellow());
+    }
+}
+
+fn run() -> Result<()> {
     let start_time = Instant::now();
+    let started = Local::now();
 
     let services_flag = Arg::new("services")
         .short('s')
         .long("services")
         .takes_value(false)
         .help("Also start auth-server and PDP after the main setup");
+    let maven_args = Arg::new("maven-args")
+        .last(true)
+        .multiple_values(true)
+        .value_name("MAVEN_ARGS")
+        .help("Extra arguments passed to Maven, e.g. runapp local -- -X");
 
     let matches = App::new("runapp")
         .version("1.0")
         .author("Gako358 <gako358@outlook.com>")
         .about("Sets up environment for running the application")
         .arg(services_flag.clone())
+        .arg(maven_args.clone())
+        .arg(
+            Arg::new("set")
+                .long("set")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .global(true)
+                .value_name("KEY=VALUE")
+                .help("Override a runapp.toml setting, e.g. --set paths.mysql_dir=db"),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .takes_value(false)
+                .global(true)
+                .help("Print every command and file change instead of performing it"),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .takes_value(false)
+                .global(true)
+                .help("Allow deleting paths outside the project and $CATALINA_HOME"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .takes_value(false)
+                .global(true)
+                .help("Print reports (status, build failures) as JSON"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .takes_value(false)
+                .global(true)
+                .help("Stream the raw Maven output while building"),
+        )
+        .arg(
+            Arg::new("rebuild")
+                .long("rebuild")
+                .takes_value(false)
+                .global(true)
+                .help("Run the Maven build even if nothing changed since the last one"),
+        )
+        .arg(
+            Arg::new("maven-profile")
+                .long("maven-profile")
+                .takes_value(true)
+                .global(true)
+                .value_name("PROFILE")
+                .help("Maven profile to build with (maven.profile)"),
+        )
+        .arg(
+            Arg::new("offline")
+                .long("offline")
+                .takes_value(false)
+                .global(true)
+                .help("Run Maven offline (maven.offline)"),
+        )
+        .arg(
+            Arg::new("with-tests")
+                .long("with-tests")
+                .takes_value(false)
+                .global(true)
+                .help("Run the tests during the Maven build (maven.skip_tests=false)"),
+        )
+        .arg(
+            Arg::new("port")
+                .long("port")
+                .takes_value(true)
+                .global(true)
+                .help("HTTP port to check and poll, matching Tomcat's server.xml (ports.http)"),
+        )
+        .arg(
+            Arg::new("debug-port")
+                .long("debug-port")
+                .takes_value(true)
+                .global(true)
+                .value_name("PORT")
+                .help("Port the JPDA debugger listens on (ports.debug)"),
+        )
+        .arg(
+            Arg::new("suspend")
+                .long("suspend")
+                .takes_value(false)
+                .global(true)
+                .help("Make Tomcat wait for a debugger to attach before starting (debug.suspend)"),
+        )
+        .arg(
+            Arg::new("no-debug")
+                .long("no-debug")
+                .takes_value(false)
+                .global(true)
+                .conflicts_with("suspend")
+                .help("Start Tomcat without the JPDA debugger (debug.enabled=false)"),
+        )
         .subcommand(
             App::new("local")
                 .about("Sets up local environment")
-                .arg(services_flag.clone()),
+                .arg(services_flag.clone())
+                .arg(maven_args.clone()),
         )
         .subcommand(
             App::new("code")
-                .about("Sets up environment for VScode")
-                .arg(services_flag.clone()),
+                .about(
+                    "Sets up environment for VScode, including its launch and task configuration",
+                )
+                .arg(services_flag.clone())
+                .arg(maven_args.clone()),
         )
         .subcommand(
             App::new("docker")
-                .about("Sets up environment for Docker")
-                .arg(services_flag.clone()),
+                .about("Builds the Docker image and runs it against the local MySQL")
+                .arg(services_flag.clone())
+                .arg(maven_args.clone()),
         )
         .subcommand(
             App::new("test")
                 .about("Sets up environment for testing")
-                .arg(services_flag.clone()),
+                .arg(services_flag.clone())
+                .arg(maven_args.clone()),
+        )
+        .subcommand(
+            App::new("redeploy")
+                .about("Rebuilds and swaps the WAR into the running Tomcat")
+                .arg(maven_args.clone()),
+        )
+        .subcommand(
+            App::new("watch")
+                .about("Rebuilds and redeploys whenever the sources change")
+                .arg(maven_args.clone()),
         )
         .subcommand(App::new("clean").about("Cleans up and stops services"))
         .subcommand(App::new("drop").about("Cleans up, stops services and drops database"))
         .subcommand(App::new("services-start").about("Start auth-server and PDP container"))
         .subcommand(App::new("services-stop").about("Stop auth-server and PDP container"))
+        .subcommand(
+            App::new("status")
+                .about("Show the state of MySQL, Tomcat, docker-compose, auth-server and PDP"),
+        )
+        .subcommand(
+            App::new("compose")
+                .about("Manage this register's docker-compose services")
+                .subcommand(
+                    App::new("up")
+                        .about("Start services in the background (all of them if none are named)")
+                        .arg(
+                            Arg::new("services")
+                                .multiple_values(true)
+                                .value_name("SERVICE"),
+                        ),
+                )
+                .subcommand(
+                    App::new("logs")
+                        .about("Show a service's logs")
+                        .arg(Arg::new("service").required(true).value_name("SERVICE"))
+                        .arg(
+                            Arg::new("follow")
+                                .short('f')
+                                .long("follow")
+                                .takes_value(false)
+                                .help("Keep printing new log lines"),
+                        ),
+                )
+                .subcommand(App::new("ps").about("List the services and whether they run")),
+        )
+        .subcommand(
+            App::new("logs")
+                .about("Show the logs of Maven, Tomcat, MySQL, auth-server and PDP as one stream")
+                .arg(
+                    Arg::new("components")
+                        .multiple_values(true)
+                        .value_name("COMPONENT")
+                        .possible_values(Component::ALL.iter().map(|component| component.name()))
+                        .help("Components to show (all of them if none are named)"),
+                )
+                .arg(
+                    Arg::new("follow")
+                        .short('f')
+                        .long("follow")
+                        .takes_value(false)
+                        .help("Keep printing new log lines"),
+                )
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .takes_value(true)
+                        .value_name("DURATION")
+                        .validator(logs::parse_since)
+                        .help("Only show lines logged within DURATION, e.g. 30s, 10m, 2h or 1d"),
+                )
+                .arg(
+                    Arg::new("grep")
+                        .long("grep")
+                        .takes_value(true)
+                        .value_name("PATTERN")
+                        .help("Only show lines containing PATTERN"),
+                ),
+        )
+        .subcommand(
+            App::new("history")
+                .about("Show recent runs and how long their steps took")
+                .arg(
+                    Arg::new("last")
+                        .long("last")
+                        .takes_value(true)
+                        .value_name("N")
+                        .validator(|value| value.parse::<usize>())
+                        .help("Show the last N runs (default 10), or compute --stats over them"),
+                )
+                .arg(
+                    Arg::new("stats")
+                        .long("stats")
+                        .takes_value(false)
+                        .help("Show the average and p95 time per step instead"),
+                ),
+        )
+        .subcommand(
+            App::new("config")
+                .about("Inspect the runapp configuration")
+                .subcommand(
+                    App::new("show")
+                        .about("Print the merged configuration and where each value came from"),
+                ),
+        )
         .get_matches();
 
-    let output = Command::new("nix-instantiate")
-        .arg("--eval")
-        .arg("--json")
-        .arg("register.nix")
-        .output()
-        .expect("Failed to execute command");
-
-    let output_str = str::from_utf8(&output.stdout).unwrap();
-    let register: Register = from_str(output_str).expect("Failed to parse JSON");
-    let register_name = Arc::new(register.register_name);
-
-    if let Some(matches) = matches.subcommand_matches("local") {
-        println!("{}", "Checking if port 8080 is in use...".yellow());
-        check_port_8080();
-        println!("{}", "Stopping running services...".red());
-        Command::new("sh")
-            .arg("-c")
-            .arg("stop_tomcat 2>/dev/null")
-            .status()
-            .expect("Failed to execute command");
-        let handle = thread::spawn(|| {
-            compile_maven().expect("Failed to compile Maven");
-        });
-        clean_local_credentials()?;
-        setup_local_database(&register_name)?;
-        start_database(&register_name)?;
-        handle.join().unwrap();
-        start_tomcat(&*register_name)?;
-        if matches.is_present("services") {
-            start_services()?;
-        }
-    } else if let Some(matches) = matches.subcommand_matches("code") {
-        println!("{}", "Checking if port 8080 is in use...".yellow());
-        check_port_8080();
-        println!("{}", "Stopping running services...".red());
-        Command::new("sh")
-            .arg("-c")
-            .arg("stop_tomcat 2>/dev/null")
-            .status()
-            .expect("Failed to execute command");
-        let handle = thread::spawn(|| {
-            compile_maven().expect("Failed to compile Maven");
-        });
-        clean_local_credentials()?;
-        setup_external_database(&*register_name)?;
-        handle.join().unwrap();
-        start_tomcat(&*register_name)?;
-        if matches.is_present("services") {
-            start_services()?;
-        }
-    } else if let Some(matches) = matches.subcommand_matches("docker") {
-        println!("{}", "Stopping running services...".red());
-        Command::new("docker-compose")
-            .arg("down")
-            .status()
-            .expect("Failed to execute command");
-        let handle = thread::spawn(|| {
-            compile_maven().expect("Failed to compile Maven");
-        });
-        clean_local_credentials()?;
-        setup_local_database(&register_name)?;
-        start_database(&register_name)?;
-        handle.join().unwrap();
-        Command::new("docker")
-            .arg("build")
-            .arg("-t")
-            .arg(format!("{}:latest", &*register_name))
-            .status()
-            .expect("Failed to execute command");
-        if matches.is_present("services") {
-            start_services()?;
-        }
-    } else if let Some(matches) = matches.subcommand_matches("test") {
-        let handle = thread::spawn(|| {
-            compile_maven().expect("Failed to compile Maven");
-        });
-        handle.join().unwrap();
-        copy_db_files().unwrap();
-        start_tomcat(&*register_name)?;
-        if matches.is_present("services") {
-            start_services()?;
-        }
-    } else if let Some(_matches) = matches.subcommand_matches("clean") {
-        stop_services()?;
-        clean_up(&*register_name)?;
-        exit_timestamp(start_time);
-        std::process::exit(0);
-    } else if let Some(_matches) = matches.subcommand_matches("drop") {
-        stop_services()?;
-        clean_up(&*register_name)?;
-        drop_database(&*register_name)?;
-        exit_timestamp(start_time);
-        std::process::exit(0);
-    } else if let Some(_matches) = matches.subcommand_matches("services-start") {
-        start_services()?;
-        exit_timestamp(start_time);
-        std::process::exit(0);
-    } else if let Some(_matches) = matches.subcommand_matches("services-stop") {
-        stop_services()?;
-        exit_timestamp(start_time);
-        std::process::exit(0);
-    } else {
-        let handle = thread::spawn(|| {
-            compile_maven().expect("Failed to compile Maven");
-        });
-        clean_local_credentials()?;
-        setup_external_database(&*register_name)?;
-        handle.join().unwrap();
-        copy_db_files().unwrap();
-        if matches.is_present("services") {
-            start_services()?;
+    let dry_run = matches.is_present("dry-run");
+    let (mode, services) = match matches.subcommand() {
+        Some((mode, sub_matches)) => (
+            mode,
+            sub_matches.try_contains_id("services").unwrap_or(false),
+        ),
+        None => ("default", matches.is_present("services")),
+    };
+
+    let mut record = RunRecord::default();
+    let result = run_matches(&matches, mode, services, start_time, &mut record);
+    if !dry_run && is_recorded(mode) {
+        let entry = history::Entry::new(
+            started,
+            mode,
+            &record.register,
+            start_time.elapsed(),
+            &record.timings,
+            result.as_ref().err(),
+        );
+        if let Err(e) = history::append(&entry) {
+            eprintln!(
+                "{}",
+                format!("Could not record this run in the history: {}", e).yellow()
+            );
+        }
+    }
+    result
+}
+
+/// What a run has got to before it ended, for its history entry.
+#[derive(Default)]
+struct RunRecord {
+    /// Empty when the register could not be loaded.
+    register: String,
+    timings: Vec<Timing>,
+}
+
+/// Whether runs of `mode` go into the history: the modes that run a
+/// pipeline, but not the ones that only inspect or follow the project.
+fn is_recorded(mode: &str) -> bool {
+    !matches!(
+        mode,
+        "config" | "history" | "status" | "watch" | "compose" | "logs"
+    )
+}
+
+fn run_matches(
+    matches: &ArgMatches,
+    mode: &str,
+    services: bool,
+    start_time: Instant,
+    record: &mut RunRecord,
+) -> Result<()> {
+    let mut overrides = Vec::new();
+    if let Some(values) = matches.values_of("set") {
+        for value in values {
+            let (key, setting) = config::parse_override(value)?;
+            overrides.push((format!("--set {}", key), key, setting));
+        }
+    }
+    if let Some(port) = matches.value_of("port") {
+        overrides.push((
+            "--port".to_string(),
+            "ports.http".to_string(),
+            port.to_string(),
+        ));
+    }
+    if let Some(port) = matches.value_of("debug-port") {
+        overrides.push((
+            "--debug-port".to_string(),
+            "ports.debug".to_string(),
+            port.to_string(),
+        ));
+    }
+    if matches.is_present("suspend") {
+        overrides.push((
+            "--suspend".to_string(),
+            "debug.suspend".to_string(),
+            "true".to_string(),
+        ));
+    }
+    if matches.is_present("no-debug") {
+        overrides.push((
+            "--no-debug".to_string(),
+            "debug.enabled".to_string(),
+            "false".to_string(),
+        ));
+    }
+    if let Some(profile) = matches.value_of("maven-profile") {
+        overrides.push((
+            "--maven-profile".to_string(),
+            "maven.profile".to_string(),
+            profile.to_string(),
+        ));
+    }
+    if matches.is_present("offline") {
+        overrides.push((
+            "--offline".to_string(),
+            "maven.offline".to_string(),
+            "true".to_string(),
+        ));
+    }
+    if matches.is_present("with-tests") {
+        overrides.push((
+            "--with-tests".to_string(),
+            "maven.skip_tests".to_string(),
+            "false".to_string(),
+        ));
+    }
+    let mut config = Config::load(&overrides)?;
+
+    if let Some(("config", _)) = matches.subcommand() {
+        if let Ok(register) = Register::load(&env::current_dir()?) {
+            config.apply_register(&register);
+        }
+        show_config(&config);
+        return Ok(());
+    }
+    if let Some(("history", sub_matches)) = matches.subcommand() {
+        let last = sub_matches
+            .value_of("last")
+            .map(|last| last.parse().expect("validated by clap"));
+        return history::show(last, sub_matches.is_present("stats"));
+    }
+
+    let register = Register::load(&env::current_dir()?)?;
+    config.apply_register(&register);
+    record.register = register.name().to_string();
+    let dry_run = matches.is_present("dry-run");
+
+    let exec = Executor::new(dry_run, DeletionPolicy::new(matches.is_present("force")));
+    let readiness = Readiness::new(&config, dry_run);
+    // Resolved before anything starts, since a conflict may move a port
+    if let Err(e) = check_ports(
+        mode,
+        &mut config,
+        &register,
+        &exec,
+        &readiness,
+        &mut record.timings,
+    ) {
+        if !dry_run {
+            print_timings(&record.timings, start_time.elapsed());
+        }
+        return Err(e);
+    }
+
+    let ctx = Context {
+        register,
+        readiness,
+        config,
+        exec,
+        json: matches.is_present("json"),
+        verbose: matches.is_present("verbose"),
+        rebuild: matches.is_present("rebuild"),
+        maven_args: passthrough_args(matches),
+    };
+    run_mode(&ctx, matches, mode, services, start_time, record)
+        .map_err(|e| ctx.attribute_program(e))
+}
+
+fn run_mode(
+    ctx: &Context,
+    matches: &ArgMatches,
+    mode: &str,
+    services: bool,
+    start_time: Instant,
+    record: &mut RunRecord,
+) -> Result<()> {
+    if mode == "status" {
+        return status::show(ctx, ctx.json);
+    }
+    if mode == "watch" {
+        return watch_sources(ctx);
+    }
+    if let Some(("compose", sub_matches)) = matches.subcommand() {
+        return compose_command(ctx, sub_matches);
+    }
+    if let Some(("logs", sub_matches)) = matches.subcommand() {
+        return logs_command(ctx, sub_matches);
+    }
+
+    let (steps, result) = mode_pipeline(mode, services).run_timed(ctx);
+    record.timings.extend(steps);
+    if let Err(e) = result {
+        if !ctx.exec.is_dry_run() {
+            print_timings(&record.timings, start_time.elapsed());
         }
+        print_build_failure_json(ctx, &e)?;
+        return Err(e);
     }
 
-    exit_timestamp(start_time);
+    if ctx.exec.is_dry_run() {
+        print_dry_run(&ctx.exec);
+        exit_timestamp(start_time, &[]);
+    } else {
+        exit_timestamp(start_time, &record.timings);
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+    use std::sync::Mutex;
+
+    /// The steps read the working directory and `CATALINA_HOME`, which are
+    /// shared by every test in the process.
+    static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+    /// Runs `mode`'s pipeline in dry-run mode against a scratch project and
+    /// returns the recorded actions, with the project directory shown as `.`.
+    fn plan(mode: &str, services: bool) -> Vec<String> {
+        let _lock = ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = env::temp_dir().join(format!("runapp-plan-{}-{}", std::process::id(), mode));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("catalina").join("logs")).unwrap();
+        fs::create_dir_all(dir.join("catalina").join("webapps")).unwrap();
+        fs::write(dir.join("register.json"), r#"{"registerName": "kvalreg"}"#).unwrap();
+        let db_source_dir = dir.join("src/main/resources/db/application");
+        fs::create_dir_all(&db_source_dir).unwrap();
+        fs::write(db_source_dir.join("schema.sql"), "").unwrap();
+        let previous = env::current_dir().unwrap();
+        env::set_current_dir(&dir).unwrap();
+        let _env = ScopedEnv::set(&[
+            ("CATALINA_HOME", dir.join("catalina")),
+            ("XDG_CONFIG_HOME", dir.join("config")),
+            ("HOME", dir.clone()),
+        ]);
+
+        let register = Register::load(&dir).unwrap();
+        let mut config = Config::load(&[]).unwrap();
+        config.apply_register(&register);
+        let ctx = Context {
+            register,
+            readiness: Readiness::new(&config, true),
+            config,
+            exec: Executor::new(true, DeletionPolicy::new(false)),
+            json: false,
+            verbose: false,
+            rebuild: false,
+            maven_args: Vec::new(),
+        };
+        let result = mode_pipeline(mode, services).run(&ctx);
+
+        env::set_current_dir(previous).unwrap();
+        let touched: Vec<&str> = ["target", "tomcat", "mysql", ".vscode"]
+            .into_iter()
+            .filter(|name| dir.join(name).exists())
+            .collect();
+        let _ = fs::remove_dir_all(&dir);
+        result.unwrap();
+        assert!(touched.is_empty(), "dry run created {:?}", touched);
+        let dir = dir.display().to_string();
+        ctx.exec
+            .actions()
+            .iter()
+            .map(|action| action.to_string().replace(&dir, "."))
+            .collect()
+    }
+
+    /// Sets environment variables for one test and clears the `RUNAPP_*`
+    /// overrides of the shell running it, putting everything back on drop.
+    struct ScopedEnv(Vec<(OsString, Option<OsString>)>);
+
+    impl ScopedEnv {
+        fn set(vars: &[(&str, PathBuf)]) -> Self {
+            let overrides = env::vars_os()
+                .map(|(key, _)| key)
+                .filter(|key| key.to_string_lossy().starts_with("RUNAPP_"));
+            let keys: Vec<OsString> = vars
+                .iter()
+                .map(|(key, _)| OsString::from(key))
+                .chain(overrides)
+                .collect();
+            let saved = keys
+                .into_iter()
+                .map(|key| {
+                    let value = env::var_os(&key);
+                    env::remove_var(&key);
+                    (key, value)
+                })
+                .collect();
+            for (key, value) in vars {
+                env::set_var(key, value);
+            }
+            ScopedEnv(saved)
+        }
+    }
+
+    impl Drop for ScopedEnv {
+        fn drop(&mut self) {
+            for (key, value) in &self.0 {
+                match value {
+                    Some(value) => env::set_var(key, value),
+                    None => env::remove_var(key),
+                }
+            }
+        }
+    }
+
+    /// Asserts that each of `expected` is part of a recorded action, in
+    /// this order.
+    fn assert_in_order(plan: &[String], expected: &[&str]) {
+        let mut from = 0;
+        for part in expected {
+            match plan[from..].iter().position(|action| action.contains(part)) {
+                Some(i) => from += i + 1,
+                None => panic!("'{}' not found in order in:\n{}", part, plan.join("\n")),
+            }
+        }
+    }
+
+    fn assert_absent(plan: &[String], part: &str) {
+        assert!(
+            plan.iter().all(|action| !action.contains(part)),
+            "'{}' found in:\n{}",
+            part,
+            plan.join("\n")
+        );
+    }
+
+    const BUILD: &str = "run    mvn clean package -DskipTests > tomcat/compile_log.txt 2>&1";
+
+    #[test]
+    fn local_sets_up_mysql_and_starts_tomcat_with_jpda() {
+        let plan = plan("local", false);
+        assert_in_order(
+            &plan,
+            &[
+                "catalina.sh stop",
+                "mysqlinit",
+                "mysqlcred",
+                "start_mysql",
+                "mysql_infile",
+                "copy   target/kvalreg.war -> ./catalina/webapps/kvalreg.war",
+                "JPDA_ADDRESS=localhost:8000 JPDA_SUSPEND=n ./catalina/bin/catalina.sh jpda start",
+            ],
+        );
+        assert_in_order(
+            &plan,
+            &["catalina.sh stop", BUILD, "catalina.sh jpda start"],
+        );
+        assert_absent(&plan, "auth-server-run");
+    }
+
+    #[test]
+    fn local_with_services_starts_them_after_tomcat() {
+        let plan = plan("local", true);
+        assert_in_order(
+            &plan,
+            &[
+                "catalina.sh jpda start",
+                "auth-server-run",
+                "pdp-docker-run",
+            ],
+        );
+    }
+
+    #[test]
+    fn code_writes_vscode_config_and_uses_the_external_database() {
+        let plan = plan("code", false);
+        assert_in_order(
+            &plan,
+            &[
+                "write  ./.vscode/launch.json",
+                "write  ./.vscode/tasks.json",
+            ],
+        );
+        assert_in_order(
+            &plan,
+            &[
+                "run    mysqlcred",
+                "run    mysqlinit_remote",
+                "catalina.sh jpda start",
+            ],
+        );
+        assert_in_order(&plan, &[BUILD, "catalina.sh jpda start"]);
+        assert_absent(&plan, "start_mysql");
+    }
+
+    #[test]
+    fn docker_builds_and_runs_the_image_instead_of_tomcat() {
+        let plan = plan("docker", false);
+        assert_in_order(
+            &plan,
+            &[
+                "start_mysql",
+                "run    docker build -t kvalreg:latest .",
+                "run    docker rm --force runapp-kvalreg",
+                "run    docker run --detach --name runapp-kvalreg --publish 8080:8080",
+            ],
+        );
+        assert_in_order(&plan, &[BUILD, "docker build"]);
+        assert_absent(&plan, "catalina.sh");
+    }
+
+    #[test]
+    fn test_copies_the_db_files_before_starting_tomcat() {
+        let plan = plan("test", false);
+        assert_in_order(
+            &plan,
+            &[
+                BUILD,
+                "copy   src/main/resources/db/application/schema.sql -> ./catalina/bin/src/main/resources/db/application/schema.sql",
+                "catalina.sh jpda start",
+            ],
+        );
+        assert_absent(&plan, "mysqlinit");
+    }
+
+    #[test]
+    fn redeploy_swaps_the_war_in_one_step() {
+        let plan = plan("redeploy", false);
+        assert_in_order(
+            &plan,
+            &[
+                BUILD,
+                "copy   target/kvalreg.war -> ./catalina/webapps/kvalreg.war.new",
+                "move   ./catalina/webapps/kvalreg.war.new -> ./catalina/webapps/kvalreg.war",
+            ],
+        );
+        assert_absent(&plan, "catalina.sh");
+    }
+
+    #[test]
+    fn clean_stops_services_tomcat_and_the_container() {
+        let plan = plan("clean", false);
+        assert_in_order(
+            &plan,
+            &[
+                "run    pdp-docker-stop",
+                "run    auth-server-stop",
+                "catalina.sh stop",
+                "run    docker rm --force runapp-kvalreg",
+            ],
+        );
+    }
+
+    #[test]
+    fn services_start_runs_auth_server_before_pdp() {
+        assert_eq!(
+            plan("services-start", false),
+            ["run    auth-server-run", "run    pdp-docker-run"]
+        );
+    }
+
+    #[test]
+    fn default_sets_up_the_external_database_and_copies_db_files() {
+        let plan = plan("default", false);
+        assert_in_order(&plan, &["run    mysqlcred", "run    mysqlinit_remote"]);
+        assert_in_order(
+            &plan,
+            &[BUILD, "copy   src/main/resources/db/application/schema.sql"],
+        );
+        assert_absent(&plan, "catalina.sh");
+    }
+}
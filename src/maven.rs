@@ -0,0 +1,416 @@
+use colored::*;
+use serde::Serialize;
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process::Command;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+/// How many lines from the end of the compile log are shown on failure.
+const TAIL_LINES: usize = 50;
+
+const SPINNER: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+/// How often a progress line is printed when stdout is not a terminal.
+const PLAIN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The Maven command line for a build, put together from the `maven.*`
+/// settings and any arguments given after `--`.
+pub struct Invocation {
+    program: String,
+    args: Vec<String>,
+}
+
+impl Invocation {
+    /// Uses the project's `./mvnw` when there is one, `maven.command` otherwise.
+    pub fn new(config: &Config, extra_args: &[String]) -> Self {
+        let program = if Path::new("mvnw").is_file() {
+            "./mvnw".to_string()
+        } else {
+            config.get("maven.command").to_string()
+        };
+
+        let mut args = vec!["clean".to_string()];
+        args.extend(
+            config
+                .get("maven.goal")
+                .split_whitespace()
+                .map(str::to_string),
+        );
+        if config.flag("maven.skip_tests") {
+            args.push("-DskipTests".to_string());
+        }
+        if config.flag("maven.offline") {
+            args.push("--offline".to_string());
+        }
+        let profile = config.get("maven.profile");
+        if !profile.is_empty() {
+            args.push(format!("-P{}", profile));
+        }
+        args.extend(
+            config
+                .get("maven.args")
+                .split_whitespace()
+                .map(str::to_string),
+        );
+        args.extend(extra_args.iter().cloned());
+
+        Invocation { program, args }
+    }
+
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        command
+    }
+
+    /// Everything about the invocation that can change the built WAR.
+    pub fn fingerprint_settings(&self) -> Vec<String> {
+        let mut settings = vec![self.program.clone()];
+        settings.extend(self.args.iter().cloned());
+        settings
+    }
+}
+
+impl fmt::Display for Invocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where the build is, as far as Maven's output tells.
+#[derive(Default)]
+struct BuildState {
+    module: Option<String>,
+    done: usize,
+    total: usize,
+}
+
+impl BuildState {
+    /// Follows `[INFO] Building <name> <version> [n/m]` lines. Plugin output
+    /// such as `Building war: ...` is ignored.
+    fn update(&mut self, line: &str) -> bool {
+        let Some(rest) = line
+            .strip_prefix("[INFO] Building ")
+            .filter(|rest| !rest.starts_with("war:") && !rest.starts_with("jar:"))
+        else {
+            if line.contains("BUILD SUCCESS") {
+                self.done = self.total;
+            }
+            return false;
+        };
+
+        let rest = rest.trim();
+        let (name, counter) = match rest.rfind('[') {
+            Some(i) if rest.ends_with(']') => {
+                (rest[..i].trim(), Some(&rest[i + 1..rest.len() - 1]))
+            }
+            _ => (rest, None),
+        };
+        match counter.and_then(|c| c.split_once('/')) {
+            Some((n, m)) => {
+                self.done = n.trim().parse::<usize>().unwrap_or(1).saturating_sub(1);
+                self.total = m.trim().parse().unwrap_or(self.total);
+            }
+            None => {
+                self.done = 0;
+                self.total = 1;
+            }
+        }
+        self.module = Some(name.to_string());
+        true
+    }
+
+    fn describe(&self, elapsed: Duration) -> String {
+        let module = self.module.as_deref().unwrap_or("starting Maven");
+        if self.total == 0 {
+            format!("{} {}", module, format_elapsed(elapsed))
+        } else {
+            format!(
+                "{} ({}/{} modules) {}",
+                module,
+                self.done,
+                self.total,
+                format_elapsed(elapsed)
+            )
+        }
+    }
+}
+
+/// Shows what Maven is doing while its output goes to the compile log: a
+/// spinner line on a terminal, a line every few seconds otherwise, or the raw
+/// output with `--verbose`.
+pub struct Progress {
+    verbose: bool,
+    interactive: bool,
+    start: Instant,
+    state: Arc<Mutex<BuildState>>,
+    ticker: Option<(Sender<()>, JoinHandle<()>)>,
+}
+
+impl Progress {
+    pub fn start(verbose: bool) -> Self {
+        let interactive = io::stdout().is_terminal();
+        let mut progress = Progress {
+            verbose,
+            interactive,
+            start: Instant::now(),
+            state: Arc::new(Mutex::new(BuildState::default())),
+            ticker: None,
+        };
+        if !verbose {
+            progress.ticker = Some(progress.spawn_ticker());
+        }
+        progress
+    }
+
+    fn spawn_ticker(&self) -> (Sender<()>, JoinHandle<()>) {
+        let (tx, rx) = mpsc::channel();
+        let state = Arc::clone(&self.state);
+        let interactive = self.interactive;
+        let start = self.start;
+        let interval = if interactive {
+            SPINNER_INTERVAL
+        } else {
+            PLAIN_INTERVAL
+        };
+
+        let handle = thread::spawn(move || {
+            let mut frame = 0;
+            while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(interval) {
+                let line = state.lock().unwrap().describe(start.elapsed());
+                if interactive {
+                    print!("\r\x1b[2K{} {}", SPINNER[frame % SPINNER.len()], line);
+                    let _ = io::stdout().flush();
+                    frame += 1;
+                } else {
+                    println!("Maven: {}", line);
+                }
+            }
+            if interactive {
+                print!("\r\x1b[2K");
+                let _ = io::stdout().flush();
+            }
+        });
+        (tx, handle)
+    }
+
+    /// Takes one line of Maven output.
+    pub fn line(&self, line: &str) {
+        if self.verbose {
+            println!("{}", line);
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.update(line) && !self.interactive {
+            println!("Maven: {}", state.describe(self.start.elapsed()));
+        }
+    }
+
+    /// Stops the live view and prints how the build went.
+    pub fn finish(mut self, success: bool) {
+        if let Some((tx, handle)) = self.ticker.take() {
+            let _ = tx.send(());
+            let _ = handle.join();
+        }
+        let message = format!(
+            "Maven build {} after {}",
+            if success { "finished" } else { "failed" },
+            format_elapsed(self.start.elapsed())
+        );
+        if success {
+            println!("{}", message.green());
+        } else {
+            println!("{}", message.red());
+        }
+    }
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// A compiler message pointing at a line in a source file.
+#[derive(Serialize)]
+pub struct Diagnostic {
+    pub line: u32,
+    pub column: u32,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct FileErrors {
+    pub file: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// What went wrong in a failed Maven build, read back from the compile log.
+#[derive(Serialize)]
+pub struct BuildSummary {
+    /// Compiler diagnostics, grouped by file in the order they were reported.
+    pub files: Vec<FileErrors>,
+    /// `[ERROR]` lines that do not point at a source file.
+    pub errors: Vec<String>,
+    /// The last lines of the log, oldest first.
+    pub tail: Vec<String>,
+}
+
+impl BuildSummary {
+    /// Parses a compile log. File paths below `root` are shown relative to it.
+    pub fn parse(log: &str, root: &Path) -> Self {
+        let lines: Vec<&str> = log.lines().collect();
+        let mut summary = BuildSummary {
+            files: Vec::new(),
+            errors: Vec::new(),
+            tail: lines[lines.len().saturating_sub(TAIL_LINES)..]
+                .iter()
+                .map(|line| line.to_string())
+                .collect(),
+        };
+
+        for line in &lines {
+            let Some(error) = line.strip_prefix("[ERROR]").map(str::trim) else {
+                continue;
+            };
+            if error.is_empty() || is_boilerplate(error) {
+                continue;
+            }
+            match parse_diagnostic(error) {
+                Some((file, diagnostic)) => {
+                    let file = Path::new(file)
+                        .strip_prefix(root)
+                        .map(|relative| relative.display().to_string())
+                        .unwrap_or_else(|_| file.to_string());
+                    summary.add(file, diagnostic);
+                }
+                None => {
+                    // Maven repeats the compiler errors in its final report
+                    if !summary.errors.iter().any(|e| e == error) {
+                        summary.errors.push(error.to_string());
+                    }
+                }
+            }
+        }
+
+        summary
+    }
+
+    fn add(&mut self, file: String, diagnostic: Diagnostic) {
+        let index = match self.files.iter().position(|f| f.file == file) {
+            Some(index) => index,
+            None => {
+                self.files.push(FileErrors {
+                    file,
+                    diagnostics: Vec::new(),
+                });
+                self.files.len() - 1
+            }
+        };
+        let diagnostics = &mut self.files[index].diagnostics;
+        let duplicate = diagnostics.iter().any(|d| {
+            d.line == diagnostic.line
+                && d.column == diagnostic.column
+                && d.message == diagnostic.message
+        });
+        if !duplicate {
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    pub fn diagnostic_count(&self) -> usize {
+        self.files.iter().map(|f| f.diagnostics.len()).sum()
+    }
+
+    /// A one-line description for the error message.
+    pub fn headline(&self) -> String {
+        let count = self.diagnostic_count();
+        if count == 0 {
+            return match self.errors.first() {
+                Some(error) => error.clone(),
+                None => "no [ERROR] lines in the compile log".to_string(),
+            };
+        }
+        format!(
+            "{} compiler error{} in {} file{}",
+            count,
+            if count == 1 { "" } else { "s" },
+            self.files.len(),
+            if self.files.len() == 1 { "" } else { "s" }
+        )
+    }
+
+    pub fn print(&self, compile_log: &str) {
+        println!(
+            "{}",
+            format!("Last {} lines of {}:", self.tail.len(), compile_log).yellow()
+        );
+        for line in &self.tail {
+            println!("  {}", line.dimmed());
+        }
+
+        if !self.files.is_empty() {
+            println!("{}", "\nCompiler errors:".red().bold());
+            for file in &self.files {
+                println!("  {}", file.file.bold());
+                for diagnostic in &file.diagnostics {
+                    println!(
+                        "    {:>5}:{:<3} {}",
+                        diagnostic.line, diagnostic.column, diagnostic.message
+                    );
+                }
+            }
+        }
+        if !self.errors.is_empty() {
+            println!("{}", "\nMaven errors:".red().bold());
+            for error in &self.errors {
+                println!("  {}", error);
+            }
+        }
+    }
+}
+
+/// Splits `/src/Foo.java:[12,5] cannot find symbol` into the file and its
+/// diagnostic.
+fn parse_diagnostic(error: &str) -> Option<(&str, Diagnostic)> {
+    let open = error.find(":[")?;
+    let rest = &error[open + 2..];
+    let close = rest.find(']')?;
+    let (line, column) = rest[..close].split_once(',')?;
+    Some((
+        &error[..open],
+        Diagnostic {
+            line: line.trim().parse().ok()?,
+            column: column.trim().parse().ok()?,
+            message: rest[close + 1..].trim().to_string(),
+        },
+    ))
+}
+
+/// Maven's advice footer, which is the same for every failure.
+fn is_boilerplate(error: &str) -> bool {
+    const FOOTER: &[&str] = &[
+        "-> [Help",
+        "[Help ",
+        "To see the full stack trace",
+        "Re-run Maven using the -X",
+        "For more information about the errors",
+        "After correcting the problems",
+        "mvn <args> -rf",
+        "COMPILATION ERROR",
+    ];
+    FOOTER.iter().any(|prefix| error.starts_with(prefix))
+}
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{Result, RunappError};
+
+type StepFn<C> = Box<dyn Fn(&C) -> Result<Outcome> + Send + Sync>;
+
+/// A named unit of work that runs once all the steps it depends on have finished.
+pub struct Step<C> {
+    name: &'static str,
+    deps: Vec<&'static str>,
+    run: StepFn<C>,
+}
+
+impl<C> Step<C> {
+    pub fn new<F>(name: &'static str, run: F) -> Self
+    where
+        F: Fn(&C) -> Result<()> + Send + Sync + 'static,
+    {
+        Step::with_outcome(name, move |ctx| run(ctx).map(|()| Outcome::Ok))
+    }
+
+    /// A step that reports how it ended, e.g. `Outcome::Skipped` when it
+    /// found there was nothing to do. Steps after it run either way.
+    pub fn with_outcome<F>(name: &'static str, run: F) -> Self
+    where
+        F: Fn(&C) -> Result<Outcome> + Send + Sync + 'static,
+    {
+        Step {
+            name,
+            deps: Vec::new(),
+            run: Box::new(run),
+        }
+    }
+
+    pub fn after(mut self, deps: &[&'static str]) -> Self {
+        self.deps.extend_from_slice(deps);
+        self
+    }
+}
+
+/// How a step of a finished run ended.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Ok,
+    Failed,
+    /// Not started, because a step before it failed, or it had nothing to
+    /// do.
+    Skipped,
+}
+
+/// How long a step ran and how it ended.
+pub struct Timing {
+    pub name: &'static str,
+    /// When the step started; for one that never started, when the run did.
+    pub started: Instant,
+    pub duration: Duration,
+    pub outcome: Outcome,
+}
+
+/// A set of steps forming a dependency graph. Steps without a dependency
+/// between them are run in parallel.
+pub struct Pipeline<C> {
+    name: &'static str,
+    steps: Vec<Step<C>>,
+}
+
+impl<C: Sync> Pipeline<C> {
+    pub fn new(name: &'static str) -> Self {
+        Pipeline {
+            name,
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn step(mut self, step: Step<C>) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// Resolves every dependency to a step index, rejecting duplicate names,
+    /// unknown dependencies and cycles.
+    fn resolve(&self) -> Result<Vec<Vec<usize>>> {
+        let mut index = HashMap::new();
+        for (i, step) in self.steps.iter().enumerate() {
+            if index.insert(step.name, i).is_some() {
+                return Err(invalid(format!(
+                    "pipeline '{}' has more than one step named '{}'",
+                    self.name, step.name
+                )));
+            }
+        }
+
+        let mut deps = Vec::with_capacity(self.steps.len());
+        for step in &self.steps {
+            let mut resolved = Vec::with_capacity(step.deps.len());
+            for dep in &step.deps {
+                match index.get(dep) {
+                    Some(&i) => resolved.push(i),
+                    None => {
+                        return Err(invalid(format!(
+                            "step '{}' in pipeline '{}' depends on unknown step '{}'",
+                            step.name, self.name, dep
+                        )))
+                    }
+                }
+            }
+            deps.push(resolved);
+        }
+
+        let mut done = vec![false; self.steps.len()];
+        let mut remaining = self.steps.len();
+        while remaining > 0 {
+            let ready: Vec<usize> = (0..self.steps.len())
+                .filter(|&i| !done[i] && deps[i].iter().all(|&d| done[d]))
+                .collect();
+            if ready.is_empty() {
+                let stuck: Vec<&str> = (0..self.steps.len())
+                    .filter(|&i| !done[i])
+                    .map(|i| self.steps[i].name)
+                    .collect();
+                return Err(invalid(format!(
+                    "pipeline '{}' has a dependency cycle between: {}",
+                    self.name,
+                    stuck.join(", ")
+                )));
+            }
+            for i in ready {
+                done[i] = true;
+                remaining -= 1;
+            }
+        }
+
+        Ok(deps)
+    }
+
+    /// Runs every step, starting each one as soon as its dependencies have
+    /// succeeded. After the first failure no new steps are started; steps
+    /// already running are allowed to finish before the error is returned.
+    pub fn run(&self, ctx: &C) -> Result<()> {
+        self.run_timed(ctx).1
+    }
+
+    /// Like `run`, and also reports the timing of every step in the order
+    /// they were added.
+    pub fn run_timed(&self, ctx: &C) -> (Vec<Timing>, Result<()>) {
+        let run_started = Instant::now();
+        let mut timings: Vec<Timing> = self
+            .steps
+            .iter()
+            .map(|step| Timing {
+                name: step.name,
+                started: run_started,
+                duration: Duration::ZERO,
+                outcome: Outcome::Skipped,
+            })
+            .collect();
+        let deps = match self.resolve() {
+            Ok(deps) => deps,
+            Err(e) => return (timings, Err(e)),
+        };
+        let (tx, rx) = mpsc::channel();
+        let mut started = vec![false; self.steps.len()];
+        let mut done = vec![false; self.steps.len()];
+        let mut running = 0;
+        let mut failure = None;
+
+        thread::scope(|scope| loop {
+            if failure.is_none() {
+                for (i, step) in self.steps.iter().enumerate() {
+                    if started[i] || !deps[i].iter().all(|&d| done[d]) {
+                        continue;
+                    }
+                    started[i] = true;
+                    running += 1;
+                    let tx = tx.clone();
+                    scope.spawn(move || {
+                        let started = Instant::now();
+                        let result = panic::catch_unwind(AssertUnwindSafe(|| (step.run)(ctx)))
+                            .unwrap_or_else(|_| Err(io::Error::other("panicked").into()));
+                        let _ = tx.send((i, result, started, started.elapsed()));
+                    });
+                }
+            }
+
+            if running == 0 {
+                break;
+            }
+
+            let (i, result, started, duration) = rx.recv().expect("pipeline step channel closed");
+            running -= 1;
+            timings[i].started = started;
+            timings[i].duration = duration;
+            match result {
+                Ok(outcome) => {
+                    done[i] = true;
+                    timings[i].outcome = outcome;
+                }
+                Err(e) => {
+                    timings[i].outcome = Outcome::Failed;
+                    if failure.is_none() {
+                        failure = Some(RunappError::StepFailed {
+                            step: self.steps[i].name,
+                            source: Box::new(e),
+                        });
+                    }
+                }
+            }
+        });
+
+        let result = match failure {
+            Some(e) => Err(e),
+            None => Ok(()),
+        };
+        (timings, result)
+    }
+}
+
+impl Timing {
+    /// Whether this step and `other` were running at the same time.
+    pub fn overlaps(&self, other: &Timing) -> bool {
+        self.started < other.started + other.duration
+            && other.started < self.started + self.duration
+    }
+}
+
+fn invalid(message: String) -> RunappError {
+    RunappError::InvalidPipeline(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    type Log = Mutex<Vec<&'static str>>;
+
+    fn logged(name: &'static str) -> Step<Log> {
+        Step::new(name, move |log: &Log| {
+            log.lock().unwrap().push(name);
+            Ok(())
+        })
+    }
+
+    fn failing(name: &'static str) -> Step<Log> {
+        Step::new(name, |_: &Log| Err(RunappError::Config("boom".to_string())))
+    }
+
+    fn position(log: &[&str], name: &str) -> usize {
+        log.iter().position(|step| *step == name).unwrap()
+    }
+
+    #[test]
+    fn runs_steps_after_their_dependencies() {
+        let log = Log::default();
+        Pipeline::new("test")
+            .step(logged("deploy").after(&["compile", "start-database"]))
+            .step(logged("start-database").after(&["setup-database"]))
+            .step(logged("setup-database"))
+            .step(logged("compile"))
+            .run(&log)
+            .unwrap();
+
+        let log = log.into_inner().unwrap();
+        assert_eq!(log.len(), 4);
+        assert!(position(&log, "setup-database") < position(&log, "start-database"));
+        assert!(position(&log, "start-database") < position(&log, "deploy"));
+        assert!(position(&log, "compile") < position(&log, "deploy"));
+    }
+
+    #[test]
+    fn stops_starting_steps_after_a_failure() {
+        let log = Log::default();
+        let (timings, result) = Pipeline::new("test")
+            .step(failing("compile"))
+            .step(logged("deploy").after(&["compile"]))
+            .run_timed(&log);
+
+        match result {
+            Err(RunappError::StepFailed { step, .. }) => assert_eq!(step, "compile"),
+            _ => panic!("expected the compile step to fail"),
+        }
+        assert!(log.into_inner().unwrap().is_empty());
+        assert!(timings[0].outcome == Outcome::Failed);
+        assert!(timings[1].outcome == Outcome::Skipped);
+    }
+
+    #[test]
+    fn runs_steps_after_one_with_nothing_to_do() {
+        let log = Log::default();
+        let (timings, result) = Pipeline::new("test")
+            .step(Step::with_outcome("compile", |_: &Log| {
+                Ok(Outcome::Skipped)
+            }))
+            .step(logged("deploy").after(&["compile"]))
+            .run_timed(&log);
+
+        result.unwrap();
+        assert_eq!(log.into_inner().unwrap(), ["deploy"]);
+        assert!(timings[0].outcome == Outcome::Skipped);
+        assert!(timings[1].outcome == Outcome::Ok);
+    }
+
+    #[test]
+    fn rejects_unknown_dependencies() {
+        let result = Pipeline::new("test")
+            .step(logged("deploy").after(&["compile"]))
+            .run(&Log::default());
+        match result {
+            Err(RunappError::InvalidPipeline(message)) => {
+                assert!(message.contains("unknown step 'compile'"))
+            }
+            _ => panic!("expected an unknown dependency error"),
+        }
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let log = Log::default();
+        let result = Pipeline::new("test")
+            .step(logged("a").after(&["b"]))
+            .step(logged("b").after(&["a"]))
+            .step(logged("c"))
+            .run(&log);
+        match result {
+            Err(RunappError::InvalidPipeline(message)) => {
+                assert!(message.contains("cycle between: a, b"))
+            }
+            _ => panic!("expected a cycle error"),
+        }
+        assert!(log.into_inner().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        let result = Pipeline::new("test")
+            .step(logged("compile"))
+            .step(logged("compile"))
+            .run(&Log::default());
+        assert!(matches!(result, Err(RunappError::InvalidPipeline(_))));
+    }
+}
@@ -0,0 +1,157 @@
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+const SYSTEM_DIRS: &[&str] = &[
+    "/bin", "/boot", "/dev", "/etc", "/home", "/lib", "/lib64", "/nix", "/opt", "/proc", "/root",
+    "/run", "/sbin", "/srv", "/sys", "/tmp", "/usr", "/var",
+];
+
+#[derive(Debug)]
+pub enum RefusalReason {
+    /// The path is `/`, a top-level directory or the home directory itself.
+    SystemDirectory,
+    /// The path is not below any of the allowed roots.
+    OutsideAllowlist,
+    /// The path looks allowed, but a symlink along it leads elsewhere.
+    SymlinkEscape(PathBuf),
+}
+
+/// A deletion the policy would not allow.
+#[derive(Debug)]
+pub struct Refusal {
+    pub path: PathBuf,
+    pub reason: RefusalReason,
+}
+
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "refusing to delete {}: ", self.path.display())?;
+        match &self.reason {
+            RefusalReason::SystemDirectory => write!(f, "it is a system directory")?,
+            RefusalReason::OutsideAllowlist => write!(
+                f,
+                "it is outside the project, $CATALINA_HOME/{{webapps,logs,bin/src}} and ~/.my.cnf"
+            )?,
+            RefusalReason::SymlinkEscape(target) => {
+                write!(f, "it resolves through a symlink to {}", target.display())?
+            }
+        }
+        write!(f, " (pass --force to delete it anyway)")
+    }
+}
+
+impl Error for Refusal {}
+
+/// Decides which paths runapp may delete: the project directory,
+/// `$CATALINA_HOME/webapps`, `logs`, `bin/src` and `compile_log.txt`, and
+/// `~/.my.cnf`. With `force` every path except `/` is allowed.
+pub struct DeletionPolicy {
+    allowed: Vec<PathBuf>,
+    home: Option<PathBuf>,
+    force: bool,
+}
+
+impl DeletionPolicy {
+    pub fn new(force: bool) -> Self {
+        let home = dirs::home_dir().map(|home| resolve(&normalize(&home)));
+        let mut policy = DeletionPolicy {
+            allowed: Vec::new(),
+            home,
+            force,
+        };
+
+        let mut allowed = Vec::new();
+        if let Ok(project) = env::current_dir() {
+            allowed.push(project);
+        }
+        if let Some(catalina_home) = env::var_os("CATALINA_HOME").filter(|v| !v.is_empty()) {
+            let catalina_home = normalize(Path::new(&catalina_home));
+            if catalina_home.is_absolute() && !policy.is_system(&resolve(&catalina_home)) {
+                allowed.push(catalina_home.join("webapps"));
+                allowed.push(catalina_home.join("logs"));
+                allowed.push(catalina_home.join("bin").join("src"));
+                allowed.push(catalina_home.join("compile_log.txt"));
+            }
+        }
+        if let Some(home) = dirs::home_dir() {
+            allowed.push(home.join(".my.cnf"));
+        }
+
+        policy.allowed = allowed
+            .iter()
+            .map(|root| resolve(&normalize(root)))
+            .filter(|root| !policy.is_system(root))
+            .collect();
+        policy
+    }
+
+    pub fn check(&self, path: &Path) -> Result<(), Refusal> {
+        let absolute = match env::current_dir() {
+            Ok(cwd) => normalize(&cwd.join(path)),
+            Err(_) => normalize(path),
+        };
+        let refuse = |reason| Refusal {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        if absolute.parent().is_none() {
+            return Err(refuse(RefusalReason::SystemDirectory));
+        }
+        if self.force {
+            return Ok(());
+        }
+        if self.is_system(&absolute) {
+            return Err(refuse(RefusalReason::SystemDirectory));
+        }
+
+        let resolved = resolve(&absolute);
+        if self.is_allowed(&resolved) {
+            return Ok(());
+        }
+        if resolved != absolute && self.is_allowed(&absolute) {
+            return Err(refuse(RefusalReason::SymlinkEscape(resolved)));
+        }
+        Err(refuse(RefusalReason::OutsideAllowlist))
+    }
+
+    fn is_allowed(&self, path: &Path) -> bool {
+        self.allowed.iter().any(|root| path.starts_with(root))
+    }
+
+    fn is_system(&self, path: &Path) -> bool {
+        path.components().count() <= 2
+            || SYSTEM_DIRS.iter().any(|dir| path == Path::new(dir))
+            || self.home.as_deref() == Some(path)
+    }
+}
+
+/// Resolves `.` and `..` without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Resolves symlinks in the parent directories of `path`. The last component
+/// is kept as is, since deleting a symlink only removes the link itself.
+fn resolve(path: &Path) -> PathBuf {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => match fs::canonicalize(parent) {
+            Ok(parent) => parent.join(name),
+            Err(_) => resolve(parent).join(name),
+        },
+        _ => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,240 @@
+use colored::*;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::Config;
+use crate::error::{Result, RunappError};
+use crate::executor::Executor;
+use crate::health::{Probe, Readiness};
+
+/// Main class of a running Tomcat, as it shows up in the command line.
+const TOMCAT_MAIN: &str = "org.apache.catalina.startup.Bootstrap";
+/// How far past a busy port to look for a free one.
+const SEARCH_RANGE: u16 = 100;
+
+/// A port runapp needs to be free before it starts something on it.
+pub struct PortSpec {
+    /// The setting holding the port, e.g. `ports.http`.
+    pub key: &'static str,
+    pub label: &'static str,
+    /// Whether runapp can make the component listen elsewhere. Tomcat's HTTP
+    /// port lives in its server.xml, so it cannot be moved from here.
+    pub movable: bool,
+}
+
+/// The process listening on a port.
+pub struct Owner {
+    pub pid: u32,
+    pub command: String,
+}
+
+impl Owner {
+    /// A Tomcat started from our `$CATALINA_HOME`, most likely left over
+    /// from an earlier run.
+    fn is_our_tomcat(&self) -> bool {
+        if !self.command.contains(TOMCAT_MAIN) {
+            return false;
+        }
+        match env::var("CATALINA_HOME") {
+            Ok(home) if !home.is_empty() => self.command.contains(&home),
+            _ => false,
+        }
+    }
+}
+
+/// Whether something is listening on `port`: either it shows up as a
+/// listening socket in `/proc/net/tcp`, or binding to it fails.
+pub fn in_use(port: u16) -> bool {
+    !listening_inodes(port).is_empty()
+        || matches!(
+            TcpListener::bind(("0.0.0.0", port)),
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse
+        )
+}
+
+/// Finds the process listening on `port`. Sockets of other users' processes
+/// cannot be traced, in which case `None` is returned.
+pub fn owner(port: u16) -> Option<Owner> {
+    let inodes = listening_inodes(port);
+    if inodes.is_empty() {
+        return None;
+    }
+    let targets: Vec<String> = inodes
+        .iter()
+        .map(|inode| format!("socket:[{}]", inode))
+        .collect();
+
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let owns_socket = fds.flatten().any(|fd| {
+            fs::read_link(fd.path())
+                .map(|link| targets.iter().any(|t| link == Path::new(t)))
+                .unwrap_or(false)
+        });
+        if owns_socket {
+            return Some(Owner {
+                pid,
+                command: command_line(pid),
+            });
+        }
+    }
+    None
+}
+
+/// Makes sure every port in `specs` is free. When one is taken and stdin is
+/// a terminal, offers to kill our own stale Tomcat or to move to the next free
+/// port; otherwise, or when the user declines, fails with `PortInUse`.
+pub fn ensure_free(
+    config: &mut Config,
+    specs: &[PortSpec],
+    exec: &Executor,
+    readiness: &Readiness,
+) -> Result<()> {
+    for spec in specs {
+        let port = config.port(spec.key);
+        println!(
+            "{}",
+            format!("Checking if port {} ({}) is in use...", port, spec.label).yellow()
+        );
+        if !in_use(port) {
+            continue;
+        }
+
+        let owner = owner(port);
+        match &owner {
+            Some(owner) => println!(
+                "{}",
+                format!(
+                    "Port {} is in use by process {}: {}",
+                    port, owner.pid, owner.command
+                )
+                .red()
+            ),
+            None => println!(
+                "{}",
+                format!("Port {} is in use by a process runapp cannot see", port).red()
+            ),
+        }
+
+        let kill = owner.as_ref().filter(|owner| owner.is_our_tomcat());
+        let next = if spec.movable {
+            next_free(config, specs, port)
+        } else {
+            None
+        };
+        let refuse = || RunappError::PortInUse {
+            port,
+            setting: spec.key,
+            movable: spec.movable,
+            pid: owner.as_ref().map(|owner| owner.pid),
+            command: owner.as_ref().map(|owner| owner.command.clone()),
+        };
+        if !io::stdin().is_terminal() || (kill.is_none() && next.is_none()) {
+            return Err(refuse());
+        }
+
+        match ask(kill, next)? {
+            Choice::Kill => {
+                let pid = kill.expect("offered only for a known owner").pid;
+                exec.status(Command::new("kill").arg(pid.to_string()))?;
+                readiness.wait_gone(spec.label, &Probe::Tcp("localhost".to_string(), port))?;
+            }
+            Choice::Move => {
+                let next = next.expect("offered only when a port was found");
+                println!(
+                    "{}",
+                    format!("Using port {} for {} instead.", next, spec.label).green()
+                );
+                config.set_picked(spec.key, next);
+            }
+            Choice::Abort => return Err(refuse()),
+        }
+    }
+
+    Ok(())
+}
+
+enum Choice {
+    Kill,
+    Move,
+    Abort,
+}
+
+fn ask(kill: Option<&Owner>, next: Option<u16>) -> Result<Choice> {
+    if kill.is_some() {
+        println!("  [k] kill it (a Tomcat left over from an earlier run)");
+    }
+    if let Some(next) = next {
+        println!("  [n] use the next free port, {}", next);
+    }
+    println!("  [a] abort");
+
+    loop {
+        print!("Choice [a]: ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if io::stdin().lock().read_line(&mut answer)? == 0 {
+            return Ok(Choice::Abort);
+        }
+        match answer.trim() {
+            "k" if kill.is_some() => return Ok(Choice::Kill),
+            "n" if next.is_some() => return Ok(Choice::Move),
+            "" | "a" => return Ok(Choice::Abort),
+            _ => println!("Please answer with one of the letters above."),
+        }
+    }
+}
+
+/// The first free port after `port` that no other spec is using.
+fn next_free(config: &Config, specs: &[PortSpec], port: u16) -> Option<u16> {
+    let taken: Vec<u16> = specs.iter().map(|spec| config.port(spec.key)).collect();
+    (port.checked_add(1)?..=port.saturating_add(SEARCH_RANGE))
+        .find(|candidate| !taken.contains(candidate) && !in_use(*candidate))
+}
+
+/// Inodes of the sockets listening on `port`, from `/proc/net/tcp` and
+/// `/proc/net/tcp6`.
+fn listening_inodes(port: u16) -> Vec<String> {
+    const LISTEN: &str = "0A";
+    let mut inodes = Vec::new();
+    for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        let Ok(contents) = fs::read_to_string(table) else {
+            continue;
+        };
+        // sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode
+        for line in contents.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 10 || fields[3] != LISTEN {
+                continue;
+            }
+            let local_port = fields[1]
+                .rsplit_once(':')
+                .and_then(|(_, hex)| u16::from_str_radix(hex, 16).ok());
+            if local_port == Some(port) && fields[9] != "0" {
+                inodes.push(fields[9].to_string());
+            }
+        }
+    }
+    inodes
+}
+
+fn command_line(pid: u32) -> String {
+    fs::read(format!("/proc/{}/cmdline", pid))
+        .map(|raw| {
+            raw.split(|&b| b == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .unwrap_or_else(|_| "(unknown command)".to_string())
+}
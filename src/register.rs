@@ -0,0 +1,131 @@
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::RunappError;
+
+type Loader = fn(&Path) -> Result<Register, String>;
+
+/// The register being run, read from `register.nix` or one of its fallbacks.
+/// Only `registerName` is required; everything else is derived from it when
+/// left out.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Register {
+    pub register_name: String,
+    #[serde(default)]
+    pub database_name: Option<String>,
+    #[serde(default)]
+    pub db_user: Option<String>,
+    #[serde(default)]
+    pub http_port: Option<u16>,
+    #[serde(default)]
+    pub debug_port: Option<u16>,
+    #[serde(default)]
+    pub war_name: Option<String>,
+    #[serde(default)]
+    pub context_path: Option<String>,
+    #[serde(skip)]
+    pub source: PathBuf,
+}
+
+impl Register {
+    /// Tries `register.nix` through `nix-instantiate` first, then
+    /// `register.json` and `register.toml` in the same directory.
+    pub fn load(dir: &Path) -> Result<Register, RunappError> {
+        let mut attempts = Vec::new();
+
+        let loaders: [(&str, Loader); 3] = [
+            ("register.nix", load_nix),
+            ("register.json", load_json),
+            ("register.toml", load_toml),
+        ];
+        for (file, loader) in loaders {
+            let path = dir.join(file);
+            if !path.exists() {
+                attempts.push(format!("  {}: not found", path.display()));
+                continue;
+            }
+            match loader(&path) {
+                Ok(mut register) => {
+                    register.source = path;
+                    return Ok(register);
+                }
+                Err(e) => attempts.push(format!("  {}: {}", path.display(), e)),
+            }
+        }
+
+        Err(RunappError::Register(format!(
+            "Could not load the register definition:\n{}",
+            attempts.join("\n")
+        )))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.register_name
+    }
+
+    pub fn database_name(&self) -> &str {
+        self.database_name.as_deref().unwrap_or(&self.register_name)
+    }
+
+    pub fn db_user(&self) -> &str {
+        self.db_user.as_deref().unwrap_or(&self.register_name)
+    }
+
+    /// Name of the WAR Maven builds, without the `.war` extension.
+    pub fn war_name(&self) -> &str {
+        self.war_name.as_deref().unwrap_or(&self.register_name)
+    }
+
+    pub fn context_path(&self) -> String {
+        match &self.context_path {
+            Some(path) => format!("/{}", path.trim_matches('/')),
+            None => format!("/{}", self.register_name),
+        }
+    }
+
+    /// Base name Tomcat maps to the context path: `/a/b` deploys as `a#b`
+    /// and `/` as `ROOT`.
+    pub fn deploy_name(&self) -> String {
+        let path = self.context_path();
+        let path = path.trim_matches('/');
+        if path.is_empty() {
+            "ROOT".to_string()
+        } else {
+            path.replace('/', "#")
+        }
+    }
+}
+
+fn load_nix(path: &Path) -> Result<Register, String> {
+    let output = Command::new("nix-instantiate")
+        .arg("--eval")
+        .arg("--json")
+        .arg("--strict")
+        .arg(path)
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => "nix-instantiate is not installed".to_string(),
+            _ => format!("failed to run nix-instantiate: {}", e),
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("nix-instantiate failed: {}", stderr.trim()));
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("unexpected nix output: {}", e))
+}
+
+fn load_json(path: &Path) -> Result<Register, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&contents).map_err(|e| e.to_string())
+}
+
+fn load_toml(path: &Path) -> Result<Register, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    toml::from_str(&contents).map_err(|e| e.to_string())
+}
@@ -0,0 +1,329 @@
+use colored::*;
+use serde::Serialize;
+use std::env;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::docker;
+use crate::error::{Result, RunappError};
+use crate::health::Probe;
+use crate::Context;
+
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    Running,
+    Degraded,
+    Stopped,
+    Unknown,
+}
+
+impl State {
+    fn colored(self) -> ColoredString {
+        match self {
+            State::Running => "running".green(),
+            State::Degraded => "degraded".yellow(),
+            State::Stopped => "stopped".red(),
+            State::Unknown => "unknown".dimmed(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ComponentStatus {
+    pub name: &'static str,
+    pub state: State,
+    pub details: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    pub register: String,
+    pub components: Vec<ComponentStatus>,
+}
+
+pub fn collect(ctx: &Context) -> Report {
+    Report {
+        register: ctx.register.name().to_string(),
+        components: vec![
+            mysql(ctx),
+            tomcat(ctx),
+            compose(ctx),
+            container(ctx),
+            auth_server(ctx),
+            pdp(ctx),
+        ],
+    }
+}
+
+pub fn show(ctx: &Context, json: bool) -> Result<()> {
+    let report = collect(ctx);
+
+    if json {
+        let out = serde_json::to_string_pretty(&report).map_err(io::Error::other)?;
+        println!("{}", out);
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        format!("Status for register {}", report.register).bright_blue()
+    );
+    println!("{}", "COMPONENT        STATE      DETAILS".bold());
+    for component in &report.components {
+        let state = component.state.colored();
+        println!(
+            "{:<16} {:<10} {}",
+            component.name,
+            state,
+            component.details.join("; ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Uses the same socket.lock and `pgrep mysqld` signals as `start_database`.
+fn mysql(ctx: &Context) -> ComponentStatus {
+    let mysql_dir = match ctx.mysql_dir() {
+        Ok(mysql_dir) => mysql_dir,
+        Err(e) => {
+            return ComponentStatus {
+                name: "mysql",
+                state: State::Unknown,
+                details: vec![e.to_string()],
+            }
+        }
+    };
+    let socket = mysql_dir.join("socket");
+    let lock_exists = mysql_dir.join("socket.lock").exists();
+    let running = process_running(ctx, Command::new("pgrep").arg("mysqld"));
+    let accepting = Probe::UnixSocket(socket.clone())
+        .check(PROBE_TIMEOUT)
+        .is_ok();
+
+    let mut details = vec![format!(
+        "socket {} {}",
+        socket.display(),
+        if accepting {
+            "accepting connections"
+        } else {
+            "not accepting connections"
+        }
+    )];
+    let state = match (lock_exists, running) {
+        (true, Some(true)) if accepting => State::Running,
+        (true, Some(true)) => State::Degraded,
+        (false, Some(true)) => {
+            details.push("mysqld is running without socket.lock".to_string());
+            State::Degraded
+        }
+        (true, Some(false)) => {
+            details.push("stale socket.lock".to_string());
+            State::Stopped
+        }
+        (false, Some(false)) => State::Stopped,
+        (_, None) => {
+            details.push("pgrep is not available".to_string());
+            State::Unknown
+        }
+    };
+    if !mysql_dir.join("data").exists() {
+        details.push("no local database".to_string());
+    }
+
+    ComponentStatus {
+        name: "mysql",
+        state,
+        details,
+    }
+}
+
+fn tomcat(ctx: &Context) -> ComponentStatus {
+    let port = ctx.config.http_port();
+    let listening = Probe::Tcp("localhost".to_string(), port)
+        .check(PROBE_TIMEOUT)
+        .is_ok();
+    let mut details = vec![format!(
+        "port {} {}",
+        port,
+        if listening { "listening" } else { "closed" }
+    )];
+
+    let deployed = match env::var("CATALINA_HOME") {
+        Ok(catalina_home) if !catalina_home.is_empty() => {
+            let webapps = Path::new(&catalina_home).join("webapps");
+            let deploy_name = ctx.register.deploy_name();
+            let war = webapps.join(format!("{}.war", deploy_name));
+            let exploded = webapps.join(&deploy_name);
+            let deployed = war.exists() || exploded.exists();
+            details.push(format!(
+                "{} {}",
+                war.display(),
+                if deployed { "deployed" } else { "not deployed" }
+            ));
+            Some(deployed)
+        }
+        _ => {
+            details.push("CATALINA_HOME is not set".to_string());
+            None
+        }
+    };
+
+    let state = match (listening, deployed) {
+        (true, Some(true)) => State::Running,
+        (true, _) => State::Degraded,
+        (false, _) => State::Stopped,
+    };
+
+    ComponentStatus {
+        name: "tomcat",
+        state,
+        details,
+    }
+}
+
+fn compose(ctx: &Context) -> ComponentStatus {
+    let services = ctx
+        .compose()
+        .and_then(|compose| compose.services(&ctx.exec));
+    let (state, details) = match services {
+        Ok(services) if services.is_empty() => {
+            (State::Stopped, vec!["no services defined".to_string()])
+        }
+        Ok(services) => {
+            let running: Vec<&str> = services
+                .iter()
+                .filter(|service| service.running)
+                .map(|service| service.name.as_str())
+                .collect();
+            if running.is_empty() {
+                (State::Stopped, vec!["no services running".to_string()])
+            } else if running.len() < services.len() {
+                (
+                    State::Degraded,
+                    vec![format!(
+                        "{} of {} running: {}",
+                        running.len(),
+                        services.len(),
+                        running.join(", ")
+                    )],
+                )
+            } else {
+                (State::Running, vec![running.join(", ")])
+            }
+        }
+        Err(RunappError::CommandNotFound { .. }) => (
+            State::Unknown,
+            vec!["docker compose is not available".to_string()],
+        ),
+        Err(RunappError::Config(_)) => (State::Unknown, vec!["no compose file".to_string()]),
+        Err(_) => (State::Unknown, vec!["docker compose ps failed".to_string()]),
+    };
+
+    ComponentStatus {
+        name: "docker-compose",
+        state,
+        details,
+    }
+}
+
+/// The app container `runapp docker` runs.
+fn container(ctx: &Context) -> ComponentStatus {
+    let name = ctx.container_name();
+    let (state, details) = match docker::is_running(&ctx.exec, &name) {
+        Some(true) => (
+            State::Running,
+            vec![format!(
+                "{} on http://localhost:{}",
+                name,
+                ctx.config.http_port()
+            )],
+        ),
+        Some(false) => (State::Stopped, vec![format!("no container {}", name)]),
+        None => (State::Unknown, vec!["docker is not available".to_string()]),
+    };
+
+    ComponentStatus {
+        name: "container",
+        state,
+        details,
+    }
+}
+
+fn auth_server(ctx: &Context) -> ComponentStatus {
+    let url = ctx.config.get("health.auth_server_url");
+    let pattern = ctx.config.get("services.auth_server_process");
+    service(
+        "auth-server",
+        url,
+        process_running(ctx, Command::new("pgrep").arg("-f").arg(pattern)),
+        format!("process matching '{}'", pattern),
+    )
+}
+
+fn pdp(ctx: &Context) -> ComponentStatus {
+    let url = ctx.config.get("health.pdp_url");
+    let container = ctx.config.get("services.pdp_container");
+    let running = ctx
+        .exec
+        .probe(
+            Command::new("docker")
+                .args(["ps", "--quiet", "--filter"])
+                .arg(format!("name={}", container)),
+        )
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| !output.stdout.is_empty());
+    service("pdp", url, running, format!("container '{}'", container))
+}
+
+fn service(name: &'static str, url: &str, running: Option<bool>, what: String) -> ComponentStatus {
+    let mut details = vec![match running {
+        Some(true) => format!("{} found", what),
+        Some(false) => format!("no {}", what),
+        None => format!("could not look for {}", what),
+    }];
+
+    let answering = if url.is_empty() {
+        None
+    } else {
+        let answering = Probe::Http(url.to_string()).check(PROBE_TIMEOUT).is_ok();
+        details.push(format!(
+            "{} {}",
+            url,
+            if answering {
+                "answering"
+            } else {
+                "not answering"
+            }
+        ));
+        Some(answering)
+    };
+
+    let state = match (running, answering) {
+        (_, Some(true)) => State::Running,
+        (Some(true), Some(false)) => State::Degraded,
+        (Some(true), None) => State::Running,
+        (Some(false), _) => State::Stopped,
+        (None, Some(false)) => State::Stopped,
+        (None, None) => State::Unknown,
+    };
+
+    ComponentStatus {
+        name,
+        state,
+        details,
+    }
+}
+
+fn process_running(ctx: &Context, command: &mut Command) -> Option<bool> {
+    ctx.exec
+        .probe(command)
+        .ok()
+        .map(|output| !output.stdout.is_empty())
+}
@@ -0,0 +1,238 @@
+use colored::*;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{Result, RunappError};
+use crate::executor::Executor;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long to wait after SIGTERM, and again after SIGKILL.
+const SIGNAL_GRACE: Duration = Duration::from_secs(5);
+const STARTED: &str = "Server startup in";
+/// catalina.out lines that mean a webapp (or Tomcat itself) failed to start.
+const FAILURES: &[&str] = &[
+    "startup failed due to previous errors",
+    "Error deploying web application",
+    "One or more listeners failed to start",
+    "One or more Filters failed to start",
+    "Failed to start component",
+    "Address already in use",
+];
+/// Lines of catalina.out kept for the error message when startup fails.
+const EXCERPT_LINES: usize = 20;
+
+/// Starts and stops the Tomcat in `$CATALINA_HOME`. Its PID is kept in the
+/// `CATALINA_PID` file so it can be stopped, and if need be killed, later.
+pub struct Tomcat {
+    home: PathBuf,
+    pid_file: PathBuf,
+}
+
+impl Tomcat {
+    pub fn new(home: &Path, pid_file: PathBuf) -> Self {
+        Tomcat {
+            home: home.to_path_buf(),
+            pid_file,
+        }
+    }
+
+    pub fn logs_dir(&self) -> PathBuf {
+        self.home.join("logs")
+    }
+
+    pub fn catalina_out(&self) -> PathBuf {
+        self.logs_dir().join("catalina.out")
+    }
+
+    /// The PID from the `CATALINA_PID` file, if that process is still alive.
+    pub fn pid(&self) -> Option<u32> {
+        let pid = fs::read_to_string(&self.pid_file)
+            .ok()?
+            .trim()
+            .parse()
+            .ok()?;
+        alive(pid).then_some(pid)
+    }
+
+    fn catalina(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(self.home.join("bin").join("catalina.sh"));
+        command.args(args).env("CATALINA_PID", &self.pid_file);
+        command
+    }
+
+    /// Runs `catalina.sh start` (`jpda start` with `jpda`) with `envs` and
+    /// waits until catalina.out reports that the server started, returning
+    /// that line (e.g. `Server startup in [3456] milliseconds`). A failure is
+    /// returned as `TomcatStart` with the lines Tomcat logged about it.
+    pub fn start(
+        &self,
+        exec: &Executor,
+        jpda: bool,
+        envs: &[(&str, String)],
+        timeout: Duration,
+    ) -> Result<String> {
+        if let Some(parent) = self.pid_file.parent() {
+            if !parent.exists() {
+                exec.create_dir_all(parent)?;
+            }
+        }
+        let log = self.catalina_out();
+        let offset = fs::metadata(&log).map(|m| m.len()).unwrap_or(0);
+
+        let mut command = if jpda {
+            self.catalina(&["jpda", "start"])
+        } else {
+            self.catalina(&["start"])
+        };
+        for (key, value) in envs {
+            command.env(key, value);
+        }
+        exec.run(&mut command)?;
+        if exec.is_dry_run() {
+            return Ok(STARTED.to_string());
+        }
+
+        let start = Instant::now();
+        let mut lines: Vec<String> = Vec::new();
+        let mut read = offset;
+        loop {
+            read = read_new_lines(&log, read, &mut lines)?;
+            if let Some(i) = lines.iter().position(|line| is_failure(line)) {
+                let end = (i + EXCERPT_LINES).min(lines.len());
+                return Err(RunappError::TomcatStart(lines[i..end].join("\n")));
+            }
+            if let Some(line) = lines.iter().find(|line| line.contains(STARTED)) {
+                return Ok(line.clone());
+            }
+
+            let exited = self.pid_file.exists() && self.pid().is_none();
+            if exited {
+                return Err(RunappError::TomcatStart(format!(
+                    "Tomcat exited during startup. Last lines of {}:\n{}",
+                    log.display(),
+                    tail(&lines)
+                )));
+            }
+            if start.elapsed() > timeout {
+                return Err(RunappError::NotReady(format!(
+                    "Tomcat did not report \"{}\" in {} within {}s",
+                    STARTED,
+                    log.display(),
+                    timeout.as_secs()
+                )));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Asks Tomcat to shut down with `catalina.sh stop`. If it is still
+    /// running after `timeout` it gets SIGTERM, and after that SIGKILL.
+    pub fn stop(&self, exec: &Executor, timeout: Duration) -> Result<()> {
+        let pid = self.pid();
+        match pid {
+            Some(pid) => println!("{}", format!("Stopping Tomcat (pid {})...", pid).yellow()),
+            None => println!("{}", "Stopping Tomcat...".yellow()),
+        }
+
+        let status = exec.status(self.catalina(&["stop"]).stderr(Stdio::null()))?;
+        let Some(pid) = pid else {
+            if !status.success() {
+                println!("{}", "Tomcat not running. Continuing...".yellow());
+            }
+            return Ok(());
+        };
+        if exec.is_dry_run() {
+            return Ok(());
+        }
+
+        let start = Instant::now();
+        if !wait_exit(pid, timeout) {
+            println!(
+                "{}",
+                format!(
+                    "Tomcat did not stop within {}s, sending SIGTERM...",
+                    timeout.as_secs()
+                )
+                .red()
+            );
+            exec.status(Command::new("kill").args(["-TERM", &pid.to_string()]))?;
+            if !wait_exit(pid, SIGNAL_GRACE) {
+                println!("{}", "Tomcat ignored SIGTERM, sending SIGKILL...".red());
+                exec.status(Command::new("kill").args(["-KILL", &pid.to_string()]))?;
+                if !wait_exit(pid, SIGNAL_GRACE) {
+                    return Err(RunappError::NotReady(format!(
+                        "Tomcat (pid {}) is still running after SIGKILL",
+                        pid
+                    )));
+                }
+            }
+        }
+        println!(
+            "{}",
+            format!("Tomcat stopped after {:.1}s", start.elapsed().as_secs_f64()).green()
+        );
+
+        if self.pid_file.exists() {
+            exec.remove(&self.pid_file)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_failure(line: &str) -> bool {
+    FAILURES.iter().any(|marker| line.contains(marker))
+}
+
+fn alive(pid: u32) -> bool {
+    // A zombie still has a /proc entry but is not running anymore
+    match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        Ok(stat) => stat
+            .rsplit_once(')')
+            .map(|(_, rest)| !rest.trim_start().starts_with('Z'))
+            .unwrap_or(true),
+        Err(_) => false,
+    }
+}
+
+fn wait_exit(pid: u32, timeout: Duration) -> bool {
+    let start = Instant::now();
+    while alive(pid) {
+        if start.elapsed() > timeout {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    true
+}
+
+/// Appends the complete lines written to `path` after `offset` and returns
+/// the offset to continue from.
+pub fn read_new_lines(path: &Path, offset: u64, lines: &mut Vec<String>) -> Result<u64> {
+    let Ok(mut file) = fs::File::open(path) else {
+        return Ok(offset);
+    };
+    let len = file.metadata()?.len();
+    // The log was truncated or rotated; start over from its beginning
+    let offset = if len < offset { 0 } else { offset };
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+
+    let Some(end) = buffer.iter().rposition(|&b| b == b'\n') else {
+        return Ok(offset);
+    };
+    lines.extend(
+        String::from_utf8_lossy(&buffer[..end])
+            .lines()
+            .map(str::to_string),
+    );
+    Ok(offset + end as u64 + 1)
+}
+
+fn tail(lines: &[String]) -> String {
+    lines[lines.len().saturating_sub(EXCERPT_LINES)..].join("\n")
+}
@@ -0,0 +1,266 @@
+use colored::*;
+use serde_json::{json, Map, Value};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::debugger;
+use crate::error::Result;
+use crate::executor::Executor;
+
+const DIR: &str = ".vscode";
+/// runapp commands offered as VS Code tasks.
+const TASKS: &[&str] = &["redeploy", "clean", "status"];
+/// Where the Java extension looks for the JDK to run the language server with.
+const JAVA_HOME_SETTING: &str = "java.jdt.ls.java.home";
+
+/// Writes runapp's entries into `.vscode/launch.json`, `tasks.json` and
+/// `settings.json` below `root`. Entries are matched by name, so the user's
+/// own configurations, tasks and settings are kept as they are.
+pub fn update(exec: &Executor, root: &Path, register_name: &str, debug_port: u16) -> Result<()> {
+    let dir = root.join(DIR);
+    if !dir.is_dir() {
+        exec.create_dir_all(&dir)?;
+    }
+
+    let launch = debugger::launch_configuration(register_name, debug_port);
+    merge(exec, &dir.join("launch.json"), |file| {
+        file.entry("version").or_insert_with(|| json!("0.2.0"));
+        upsert(file, "configurations", "name", vec![launch])
+    })?;
+
+    let tasks = TASKS
+        .iter()
+        .map(|task| {
+            json!({
+                "label": format!("runapp: {}", task),
+                "type": "shell",
+                "command": format!("runapp {}", task),
+                "problemMatcher": [],
+            })
+        })
+        .collect();
+    merge(exec, &dir.join("tasks.json"), |file| {
+        file.entry("version").or_insert_with(|| json!("2.0.0"));
+        upsert(file, "tasks", "label", tasks)
+    })?;
+
+    match env::var("JAVA_HOME") {
+        Ok(java_home) if !java_home.is_empty() => {
+            merge(exec, &dir.join("settings.json"), |file| {
+                file.insert(JAVA_HOME_SETTING.to_string(), json!(java_home));
+                Ok(())
+            })?;
+        }
+        _ => println!(
+            "{}",
+            format!(
+                "JAVA_HOME is not set, leaving {} out of {}/settings.json.",
+                JAVA_HOME_SETTING, DIR
+            )
+            .yellow()
+        ),
+    }
+    Ok(())
+}
+
+/// Applies `edit` to the JSON object in `path` and writes it back if that
+/// changed anything. A file runapp cannot parse or `edit` refuses, and one
+/// with comments that writing it back would drop, is left alone.
+fn merge<F>(exec: &Executor, path: &Path, edit: F) -> Result<()>
+where
+    F: FnOnce(&mut Map<String, Value>) -> std::result::Result<(), String>,
+{
+    let shown = Path::new(DIR).join(path.file_name().unwrap_or_default());
+    let existing = match fs::read_to_string(path) {
+        Ok(contents) => Some(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut file = match &existing {
+        None => Map::new(),
+        Some(contents) => match serde_json::from_str(&strip_jsonc(contents)) {
+            Ok(Value::Object(file)) => file,
+            Ok(_) => return skip(&shown, "it is not a JSON object"),
+            Err(e) => return skip(&shown, &format!("it could not be read: {}", e)),
+        },
+    };
+    let before = Value::Object(file.clone());
+    if let Err(reason) = edit(&mut file) {
+        return skip(&shown, &reason);
+    }
+    let after = Value::Object(file);
+    if existing.is_some() && before == after {
+        println!("{}", format!("{} is up to date.", shown.display()).green());
+        return Ok(());
+    }
+    if existing
+        .as_ref()
+        .is_some_and(|contents| strip_comments(contents) != *contents)
+    {
+        return skip(
+            &shown,
+            "writing runapp's entries into it would drop its comments. \
+             Remove them to let runapp update it",
+        );
+    }
+
+    let contents = serde_json::to_string_pretty(&after).map_err(std::io::Error::other)?;
+    exec.write(path, &format!("{}\n", contents))?;
+    match &existing {
+        None => println!("{}", format!("Created {}", shown.display()).green()),
+        Some(_) => println!("{}", format!("Updated {}", shown.display()).green()),
+    }
+    Ok(())
+}
+
+fn skip(shown: &Path, reason: &str) -> Result<()> {
+    println!(
+        "{}",
+        format!("Leaving {} alone, {}.", shown.display(), reason).yellow()
+    );
+    Ok(())
+}
+
+/// Writes runapp's fields of `entries` into the entries of the array `field`
+/// whose `key` matches, keeping any fields the user added to them, and
+/// appends the others. Fails when `field` is there but not an array.
+fn upsert(
+    file: &mut Map<String, Value>,
+    field: &str,
+    key: &str,
+    entries: Vec<Value>,
+) -> std::result::Result<(), String> {
+    let list = file
+        .entry(field.to_string())
+        .or_insert_with(|| Value::Array(Vec::new()));
+    let Value::Array(list) = list else {
+        return Err(format!("its \"{}\" is not a list", field));
+    };
+    for entry in entries {
+        match list
+            .iter_mut()
+            .find(|existing| existing.get(key) == entry.get(key))
+        {
+            Some(Value::Object(existing)) => {
+                let Value::Object(fields) = entry else {
+                    unreachable!("runapp's entries are objects");
+                };
+                existing.extend(fields);
+            }
+            _ => list.push(entry),
+        }
+    }
+    Ok(())
+}
+
+/// Turns VS Code's JSON with comments into plain JSON: drops `//` and `/* */`
+/// comments, then trailing commas, leaving strings untouched.
+fn strip_jsonc(input: &str) -> String {
+    scan(&strip_comments(input), |chars, i, out| {
+        let trailing = chars[i] == ','
+            && matches!(
+                chars[i + 1..].iter().find(|c| !c.is_whitespace()),
+                Some('}') | Some(']')
+            );
+        if !trailing {
+            out.push(chars[i]);
+        }
+        i + 1
+    })
+}
+
+fn strip_comments(input: &str) -> String {
+    scan(input, |chars, i, out| match chars[i] {
+        '/' if chars.get(i + 1) == Some(&'/') => {
+            let mut end = i;
+            while end < chars.len() && chars[end] != '\n' {
+                end += 1;
+            }
+            end
+        }
+        '/' if chars.get(i + 1) == Some(&'*') => {
+            let mut end = i + 2;
+            while end < chars.len() && !(chars[end] == '*' && chars.get(end + 1) == Some(&'/')) {
+                end += 1;
+            }
+            (end + 2).min(chars.len())
+        }
+        c => {
+            out.push(c);
+            i + 1
+        }
+    })
+}
+
+/// Copies strings in `input` as they are and hands everything else to
+/// `other`, which appends what to keep and returns where to continue.
+fn scan<F>(input: &str, mut other: F) -> String
+where
+    F: FnMut(&[char], usize, &mut String) -> usize,
+{
+    let chars: Vec<char> = input.chars().collect();
+    let mut out = String::with_capacity(input.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '"' {
+            i = other(&chars, i, &mut out);
+            continue;
+        }
+        out.push('"');
+        i += 1;
+        while i < chars.len() && chars[i] != '"' {
+            if chars[i] == '\\' && i + 1 < chars.len() {
+                out.push(chars[i]);
+                i += 1;
+            }
+            out.push(chars[i]);
+            i += 1;
+        }
+        if i < chars.len() {
+            out.push('"');
+            i += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(contents: Value) -> Map<String, Value> {
+        let Value::Object(file) = contents else {
+            panic!("not an object");
+        };
+        file
+    }
+
+    #[test]
+    fn upsert_keeps_the_users_fields_of_a_matching_entry() {
+        let mut tasks = file(json!({
+            "tasks": [
+                {"label": "runapp: clean", "command": "old", "dependsOn": "build"},
+                {"label": "build", "command": "make"},
+            ]
+        }));
+        let ours = json!({"label": "runapp: clean", "command": "runapp clean"});
+        upsert(&mut tasks, "tasks", "label", vec![ours]).unwrap();
+        assert_eq!(
+            tasks["tasks"],
+            json!([
+                {"label": "runapp: clean", "command": "runapp clean", "dependsOn": "build"},
+                {"label": "build", "command": "make"},
+            ])
+        );
+    }
+
+    #[test]
+    fn upsert_refuses_a_field_that_is_not_a_list() {
+        let mut launch = file(json!({"configurations": {"name": "mine"}}));
+        let ours = json!({"name": "Attach to Tomcat"});
+        assert!(upsert(&mut launch, "configurations", "name", vec![ours]).is_err());
+        assert_eq!(launch["configurations"], json!({"name": "mine"}));
+    }
+}
@@ -0,0 +1,109 @@
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use crate::error::Result;
+
+/// How long the tree has to be quiet before a burst of changes is handled,
+/// so saving several files (or a `git checkout`) triggers one rebuild.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+/// Watched recursively, relative to the project directory.
+const SOURCES: &str = "src/main";
+const POM: &str = "pom.xml";
+
+/// What a burst of changes calls for.
+pub enum Change {
+    /// Sources or the POM changed: build and redeploy the WAR.
+    Build(Vec<PathBuf>),
+    /// Only the database scripts changed: copying them over is enough.
+    DbFiles(Vec<PathBuf>),
+}
+
+/// Watches the project's sources and `pom.xml` through inotify.
+pub struct SourceWatcher {
+    events: Receiver<notify::Result<Event>>,
+    sources: PathBuf,
+    pom: PathBuf,
+    db_dir: PathBuf,
+    // Dropping the watcher stops the events
+    _watcher: RecommendedWatcher,
+}
+
+impl SourceWatcher {
+    pub fn new(root: &Path, db_source_dir: &str) -> Result<Self> {
+        let sources = root.join(SOURCES);
+        let (tx, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx).map_err(watch_error)?;
+        watcher
+            .watch(&sources, RecursiveMode::Recursive)
+            .map_err(watch_error)?;
+        // Editors replace files on save, so the POM is watched through its
+        // directory rather than by itself
+        watcher
+            .watch(root, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+
+        Ok(SourceWatcher {
+            events,
+            sources,
+            pom: root.join(POM),
+            db_dir: root.join(db_source_dir),
+            _watcher: watcher,
+        })
+    }
+
+    /// Blocks until something relevant changed and the changes have settled.
+    pub fn next(&self) -> Result<Change> {
+        let mut changed: Vec<PathBuf> = Vec::new();
+        loop {
+            let event = if changed.is_empty() {
+                self.events.recv().map_err(|_| watcher_gone())?
+            } else {
+                match self.events.recv_timeout(DEBOUNCE) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return Err(watcher_gone().into()),
+                }
+            };
+            let event = event.map_err(watch_error)?;
+            if !matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) {
+                continue;
+            }
+            for path in event.paths {
+                if self.is_relevant(&path) && !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+
+        if changed.iter().all(|path| path.starts_with(&self.db_dir)) {
+            Ok(Change::DbFiles(changed))
+        } else {
+            Ok(Change::Build(changed))
+        }
+    }
+
+    fn is_relevant(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        // Swap and backup files, and the file vim creates to test the directory
+        if name.starts_with('.') || name.ends_with('~') || name == "4913" {
+            return false;
+        }
+        path == self.pom || path.starts_with(&self.sources)
+    }
+}
+
+fn watch_error(e: notify::Error) -> io::Error {
+    io::Error::other(format!("cannot watch the sources: {}", e))
+}
+
+fn watcher_gone() -> io::Error {
+    io::Error::other("the file watcher stopped")
+}